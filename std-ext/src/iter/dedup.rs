@@ -0,0 +1,388 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    hash::Hash,
+    time::{Duration, Instant},
+};
+
+use crate::time::Clock;
+
+/// Decides whether a key has been seen before.
+pub trait Seen<K> {
+    /// Records the key, returning `true` if it should be yielded (i.e. it has
+    /// not been seen before) and `false` if it is a duplicate.
+    fn insert(&mut self, key: K) -> bool;
+}
+
+pub trait DedupExt: Iterator + Sized {
+    /// Drops items whose key has been seen among the last `capacity` distinct
+    /// keys. Seeing a duplicate refreshes its key as the most recently used.
+    fn dedup_by_key_lru<K, F>(self, capacity: usize, key: F) -> Dedup<Self, F, Lru<K>>
+    where
+        K: Hash + Eq + Clone,
+        F: FnMut(&Self::Item) -> K,
+    {
+        Dedup::new(self, key, Lru::new(capacity))
+    }
+
+    /// Drops items whose key has been seen within the last `ttl`. At most
+    /// `capacity` keys are remembered; the oldest ones are forgotten first.
+    fn dedup_by_key_expiring<K, F, C>(
+        self,
+        clock: C,
+        ttl: Duration,
+        capacity: usize,
+        key: F,
+    ) -> Dedup<Self, F, Expiring<K, C>>
+    where
+        K: Hash + Eq + Clone,
+        C: Clock,
+        F: FnMut(&Self::Item) -> K,
+    {
+        Dedup::new(self, key, Expiring::new(clock, ttl, capacity))
+    }
+
+    /// Drops items whose sequence number is not greater than the greatest one
+    /// yielded so far, i.e. stale, duplicate and out-of-order items.
+    fn dedup_by_sequence<N, F>(self, sequence: F) -> Dedup<Self, F, Sequence<N>>
+    where
+        N: Ord,
+        F: FnMut(&Self::Item) -> N,
+    {
+        Dedup::new(self, sequence, Sequence::new())
+    }
+}
+
+impl<I> DedupExt for I where I: Iterator {}
+
+/// Deduplicating adapter for iterators and (with the `stream` feature)
+/// streams.
+///
+/// Useful on top of at-least-once sources, such as a websocket stream
+/// stitched over a reconnect.
+pub struct Dedup<I, F, S> {
+    inner: I,
+    key: F,
+    seen: S,
+}
+
+impl<I, F, S> Dedup<I, F, S> {
+    pub fn new(inner: I, key: F, seen: S) -> Self {
+        Self { inner, key, seen }
+    }
+}
+
+impl<I, F, S, K> Iterator for Dedup<I, F, S>
+where
+    I: Iterator,
+    F: FnMut(&I::Item) -> K,
+    S: Seen<K>,
+{
+    type Item = I::Item;
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        let Self { inner, key, seen } = self;
+        inner.find(|item| seen.insert(key(item)))
+    }
+}
+
+#[cfg(feature = "stream")]
+pub use self::stream::*;
+
+#[cfg(feature = "stream")]
+mod stream {
+    use std::{
+        pin::Pin,
+        task::{Context, Poll, ready},
+    };
+
+    use futures_core::Stream;
+
+    use super::*;
+
+    pub trait StreamDedupExt: Stream + Sized {
+        /// Stream counterpart of [`DedupExt::dedup_by_key_lru`].
+        fn dedup_by_key_lru<K, F>(self, capacity: usize, key: F) -> Dedup<Self, F, Lru<K>>
+        where
+            K: Hash + Eq + Clone,
+            F: FnMut(&Self::Item) -> K,
+        {
+            Dedup::new(self, key, Lru::new(capacity))
+        }
+
+        /// Stream counterpart of [`DedupExt::dedup_by_key_expiring`].
+        fn dedup_by_key_expiring<K, F, C>(
+            self,
+            clock: C,
+            ttl: Duration,
+            capacity: usize,
+            key: F,
+        ) -> Dedup<Self, F, Expiring<K, C>>
+        where
+            K: Hash + Eq + Clone,
+            C: Clock,
+            F: FnMut(&Self::Item) -> K,
+        {
+            Dedup::new(self, key, Expiring::new(clock, ttl, capacity))
+        }
+
+        /// Stream counterpart of [`DedupExt::dedup_by_sequence`].
+        fn dedup_by_sequence<N, F>(self, sequence: F) -> Dedup<Self, F, Sequence<N>>
+        where
+            N: Ord,
+            F: FnMut(&Self::Item) -> N,
+        {
+            Dedup::new(self, sequence, Sequence::new())
+        }
+    }
+
+    impl<S> StreamDedupExt for S where S: Stream {}
+
+    impl<I: Unpin, F, S> Unpin for Dedup<I, F, S> {}
+
+    impl<I, F, S, K> Stream for Dedup<I, F, S>
+    where
+        I: Stream + Unpin,
+        F: FnMut(&I::Item) -> K,
+        S: Seen<K>,
+    {
+        type Item = I::Item;
+
+        fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+            let this = self.get_mut();
+            loop {
+                let Some(item) = ready!(Pin::new(&mut this.inner).poll_next(cx)) else {
+                    return Poll::Ready(None);
+                };
+                if this.seen.insert((this.key)(&item)) {
+                    return Poll::Ready(Some(item));
+                }
+            }
+        }
+    }
+}
+
+/// Remembers the most recently used keys up to a capacity.
+pub struct Lru<K> {
+    capacity: usize,
+    stamps: HashMap<K, u64>,
+    // Keys in order of use. A key may appear more than once; only the entry
+    // matching its stamp in `stamps` is current.
+    order: VecDeque<(K, u64)>,
+    next_stamp: u64,
+}
+
+impl<K> Lru<K> {
+    #[must_use]
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            stamps: HashMap::with_capacity(capacity),
+            order: VecDeque::with_capacity(capacity),
+            next_stamp: 0,
+        }
+    }
+}
+
+impl<K> Seen<K> for Lru<K>
+where
+    K: Hash + Eq + Clone,
+{
+    fn insert(&mut self, key: K) -> bool {
+        let stamp = self.next_stamp;
+        self.next_stamp += 1;
+        let is_new = self.stamps.insert(key.clone(), stamp).is_none();
+        self.order.push_back((key, stamp));
+
+        while self.stamps.len() > self.capacity {
+            let Some((key, stamp)) = self.order.pop_front() else {
+                break;
+            };
+            if self.stamps.get(&key) == Some(&stamp) {
+                self.stamps.remove(&key);
+            }
+        }
+        // Drop outdated entries left behind by refreshed keys so that the
+        // order queue stays bounded even if no new keys arrive.
+        if self.order.len() > self.capacity.saturating_mul(2) {
+            let stamps = &self.stamps;
+            self.order
+                .retain(|(key, stamp)| stamps.get(key) == Some(stamp));
+        }
+
+        is_new
+    }
+}
+
+/// Remembers keys for a time-to-live, read through a [`Clock`], up to a
+/// capacity.
+pub struct Expiring<K, C> {
+    clock: C,
+    ttl: Duration,
+    capacity: usize,
+    // `None` if the deadline is too far in the future to represent, i.e. the
+    // key never expires.
+    deadlines: HashMap<K, Option<Instant>>,
+    // Keys in order of insertion. Each key appears exactly once.
+    order: VecDeque<K>,
+}
+
+impl<K, C> Expiring<K, C> {
+    pub fn new(clock: C, ttl: Duration, capacity: usize) -> Self {
+        Self {
+            clock,
+            ttl,
+            capacity,
+            deadlines: HashMap::with_capacity(capacity),
+            order: VecDeque::with_capacity(capacity),
+        }
+    }
+}
+
+impl<K, C> Seen<K> for Expiring<K, C>
+where
+    K: Hash + Eq + Clone,
+    C: Clock,
+{
+    fn insert(&mut self, key: K) -> bool {
+        let now = self.clock.now();
+
+        // Keys expire in insertion order, so only the front needs checking.
+        while let Some(front) = self.order.front() {
+            if self
+                .deadlines
+                .get(front)
+                .is_some_and(|deadline| deadline.is_none_or(|deadline| now < deadline))
+            {
+                break;
+            }
+            if let Some(front) = self.order.pop_front() {
+                self.deadlines.remove(&front);
+            }
+        }
+
+        if self.deadlines.contains_key(&key) {
+            return false;
+        }
+        if self.capacity == 0 {
+            return true;
+        }
+        if self.order.len() >= self.capacity
+            && let Some(oldest) = self.order.pop_front()
+        {
+            self.deadlines.remove(&oldest);
+        }
+        self.deadlines
+            .insert(key.clone(), now.checked_add(self.ttl));
+        self.order.push_back(key);
+        true
+    }
+}
+
+/// Remembers the greatest sequence number yielded so far.
+pub struct Sequence<N> {
+    last: Option<N>,
+}
+
+impl<N> Sequence<N> {
+    #[must_use]
+    pub const fn new() -> Self {
+        Self { last: None }
+    }
+}
+
+impl<N> Default for Sequence<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<N> Seen<N> for Sequence<N>
+where
+    N: Ord,
+{
+    fn insert(&mut self, key: N) -> bool {
+        if self.last.as_ref().is_some_and(|last| key <= *last) {
+            return false;
+        }
+        self.last = Some(key);
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::Cell, rc::Rc};
+
+    use super::*;
+    use crate::time::tests::MockClock;
+
+    #[test]
+    fn lru_forgets_least_recently_used() {
+        let items = [1, 2, 1, 3, 2, 1, 3];
+        let deduped = items
+            .into_iter()
+            .dedup_by_key_lru(2, |item| *item)
+            .collect::<Vec<_>>();
+        // 2 is evicted when 3 arrives since 1 was used more recently. 1 is
+        // evicted when 2 arrives again.
+        assert_eq!(deduped, vec![1, 2, 3, 2, 1, 3]);
+    }
+
+    #[test]
+    fn expiring_forgets_after_ttl() {
+        let start = Instant::now();
+        let cell = Rc::new(Cell::new(start));
+        let mut seen = Expiring::new(MockClock(cell.clone()), Duration::from_nanos(2), 8);
+
+        assert!(seen.insert("a"));
+        cell.set(start + Duration::from_nanos(1));
+        assert!(!seen.insert("a"));
+        cell.set(start + Duration::from_nanos(2));
+        assert!(seen.insert("a"));
+
+        let mut seen = Expiring::new(MockClock(cell.clone()), Duration::MAX, 8);
+        assert!(seen.insert("a"));
+        assert!(!seen.insert("a"));
+    }
+
+    #[test]
+    fn sequence_drops_stale_and_out_of_order() {
+        let items = [1, 2, 2, 4, 3, 5];
+        let deduped = items
+            .into_iter()
+            .dedup_by_sequence(|item| *item)
+            .collect::<Vec<_>>();
+        assert_eq!(deduped, vec![1, 2, 4, 5]);
+    }
+
+    #[cfg(feature = "stream")]
+    #[test]
+    fn stream_dedup_by_key_lru() {
+        use std::{
+            pin::Pin,
+            task::{Context, Poll, Waker},
+        };
+
+        use futures_core::Stream;
+
+        struct Iter<I>(I);
+
+        impl<I: Iterator + Unpin> Stream for Iter<I> {
+            type Item = I::Item;
+
+            fn poll_next(mut self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<Option<I::Item>> {
+                Poll::Ready(self.0.next())
+            }
+        }
+
+        let mut stream =
+            StreamDedupExt::dedup_by_key_lru(Iter([1, 2, 1, 3].into_iter()), 2, |item| *item);
+        let mut cx = Context::from_waker(Waker::noop());
+        let mut deduped = Vec::new();
+        while let Poll::Ready(Some(item)) = Pin::new(&mut stream).poll_next(&mut cx) {
+            deduped.push(item);
+        }
+        assert_eq!(deduped, vec![1, 2, 3]);
+    }
+}
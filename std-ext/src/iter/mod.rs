@@ -1,3 +1,4 @@
+mod dedup;
 mod exponential;
 mod reset;
 mod saturating;
@@ -7,7 +8,7 @@ use std::{
     time::Duration,
 };
 
-pub use self::{exponential::*, reset::*, saturating::*};
+pub use self::{dedup::*, exponential::*, reset::*, saturating::*};
 use crate::time::{Clock, MonotonicClock};
 
 pub type ZeroThenExponentialWithReset =
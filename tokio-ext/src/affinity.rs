@@ -0,0 +1,55 @@
+use std::io;
+
+/// Pins the current thread to the given CPU cores with `sched_setaffinity`,
+/// so that the scheduler only runs it on those cores.
+///
+/// Only supported on Linux; returns [`io::ErrorKind::Unsupported`] elsewhere.
+#[cfg(target_os = "linux")]
+pub fn pin_current_thread(cores: &[usize]) -> io::Result<()> {
+    // SAFETY: `cpu_set_t` is a plain bit mask for which all zeros is valid.
+    let mut set = unsafe { std::mem::zeroed::<libc::cpu_set_t>() };
+    for &core in cores {
+        if core >= libc::CPU_SETSIZE as usize {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("CPU core {core} out of range."),
+            ));
+        }
+        // SAFETY: The core was checked to be within the set.
+        unsafe { libc::CPU_SET(core, &mut set) };
+    }
+
+    // SAFETY: The set is initialized and its size is passed along. Pid 0 is the
+    // calling thread.
+    let result = unsafe {
+        libc::sched_setaffinity(0, std::mem::size_of::<libc::cpu_set_t>(), &raw const set)
+    };
+    if result == 0 {
+        Ok(())
+    } else {
+        Err(io::Error::last_os_error())
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn pin_current_thread(_cores: &[usize]) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "Pinning threads to CPU cores is only supported on Linux.",
+    ))
+}
+
+#[cfg(test)]
+#[cfg(target_os = "linux")]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pin_current_thread_rejects_out_of_range_core() {
+        let error = std::thread::spawn(|| pin_current_thread(&[usize::MAX]))
+            .join()
+            .unwrap()
+            .unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+    }
+}
@@ -0,0 +1,230 @@
+use std::{
+    panic::Location,
+    pin::Pin,
+    task::{Context, Poll},
+    time::{Duration, Instant},
+};
+
+use pin_project_lite::pin_project;
+use tokio::time::{Sleep, Timeout, error::Elapsed, sleep, timeout};
+#[cfg(feature = "cancel")]
+use tokio_util::sync::{CancellationToken, WaitForCancellationFutureOwned};
+use tracing::warn;
+
+pub trait FutureExt: Future + Sized {
+    /// Like [`tokio::time::timeout`], but logs with the given name and the
+    /// caller location when the timeout elapses.
+    #[track_caller]
+    fn timeout_named(self, name: &'static str, duration: Duration) -> TimeoutNamed<Self> {
+        TimeoutNamed {
+            inner: timeout(duration, self),
+            name,
+            duration,
+            caller: Location::caller(),
+        }
+    }
+
+    /// Completes with [`Cancelled`] as soon as the token is cancelled, dropping
+    /// the future.
+    #[cfg(feature = "cancel")]
+    fn with_cancel(self, token: CancellationToken) -> WithCancel<Self> {
+        WithCancel {
+            inner: self,
+            cancelled: token.cancelled_owned(),
+        }
+    }
+
+    /// Warns once if the future is still pending after `threshold`, and again
+    /// when it eventually completes, to find futures stuck on a slow
+    /// dependency.
+    #[track_caller]
+    fn log_slow(self, threshold: Duration) -> LogSlow<Self> {
+        LogSlow {
+            inner: self,
+            sleep: sleep(threshold),
+            threshold,
+            started: Instant::now(),
+            slow: false,
+            caller: Location::caller(),
+        }
+    }
+
+    /// Measures the time spent polling the future, i.e. the time it occupied a
+    /// runtime thread. A future with a large [`PollTime::max`] blocks the
+    /// runtime.
+    fn instrumented_poll_time(self) -> InstrumentedPollTime<Self> {
+        InstrumentedPollTime {
+            inner: self,
+            poll_time: PollTime::default(),
+        }
+    }
+}
+
+impl<F> FutureExt for F where F: Future {}
+
+pin_project! {
+    pub struct TimeoutNamed<F> {
+        #[pin]
+        inner: Timeout<F>,
+        name: &'static str,
+        duration: Duration,
+        caller: &'static Location<'static>,
+    }
+}
+
+impl<F> Future for TimeoutNamed<F>
+where
+    F: Future,
+{
+    type Output = Result<F::Output, Elapsed>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.project();
+        let poll = this.inner.poll(cx);
+        if let Poll::Ready(Err(_)) = poll {
+            warn!(
+                future = *this.name,
+                timeout = ?this.duration,
+                file = this.caller.file(),
+                line = this.caller.line(),
+                "Future timed out."
+            );
+        }
+        poll
+    }
+}
+
+#[cfg(feature = "cancel")]
+#[derive(thiserror::Error, Debug, PartialEq, Eq)]
+#[error("Future was cancelled.")]
+pub struct Cancelled;
+
+#[cfg(feature = "cancel")]
+pin_project! {
+    pub struct WithCancel<F> {
+        #[pin]
+        inner: F,
+        #[pin]
+        cancelled: WaitForCancellationFutureOwned,
+    }
+}
+
+#[cfg(feature = "cancel")]
+impl<F> Future for WithCancel<F>
+where
+    F: Future,
+{
+    type Output = Result<F::Output, Cancelled>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.project();
+        if this.cancelled.poll(cx).is_ready() {
+            return Poll::Ready(Err(Cancelled));
+        }
+        this.inner.poll(cx).map(Ok)
+    }
+}
+
+pin_project! {
+    pub struct LogSlow<F> {
+        #[pin]
+        inner: F,
+        #[pin]
+        sleep: Sleep,
+        threshold: Duration,
+        started: Instant,
+        slow: bool,
+        caller: &'static Location<'static>,
+    }
+}
+
+impl<F> Future for LogSlow<F>
+where
+    F: Future,
+{
+    type Output = F::Output;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.project();
+        if let Poll::Ready(output) = this.inner.poll(cx) {
+            if *this.slow {
+                warn!(
+                    elapsed = ?this.started.elapsed(),
+                    file = this.caller.file(),
+                    line = this.caller.line(),
+                    "Slow future completed."
+                );
+            }
+            return Poll::Ready(output);
+        }
+
+        if !*this.slow && this.sleep.poll(cx).is_ready() {
+            *this.slow = true;
+            warn!(
+                threshold = ?this.threshold,
+                file = this.caller.file(),
+                line = this.caller.line(),
+                "Future pending longer than threshold."
+            );
+        }
+        Poll::Pending
+    }
+}
+
+/// Time spent polling a future. See [`FutureExt::instrumented_poll_time`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct PollTime {
+    pub polls: u64,
+    pub total: Duration,
+    /// Longest single poll.
+    pub max: Duration,
+}
+
+pin_project! {
+    pub struct InstrumentedPollTime<F> {
+        #[pin]
+        inner: F,
+        poll_time: PollTime,
+    }
+}
+
+impl<F> Future for InstrumentedPollTime<F>
+where
+    F: Future,
+{
+    type Output = (F::Output, PollTime);
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.project();
+        let start = Instant::now();
+        let poll = this.inner.poll(cx);
+        let elapsed = start.elapsed();
+
+        this.poll_time.polls += 1;
+        this.poll_time.total += elapsed;
+        this.poll_time.max = this.poll_time.max.max(elapsed);
+        poll.map(|output| (output, *this.poll_time))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::future::pending;
+
+    use super::*;
+
+    #[tokio::test(start_paused = true)]
+    async fn timeout_named_elapses() {
+        let result = pending::<()>()
+            .timeout_named("pending", Duration::from_secs(1))
+            .await;
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn instrumented_poll_time_counts_polls() {
+        let ((), poll_time) = tokio::task::yield_now().instrumented_poll_time().await;
+        assert_eq!(poll_time.polls, 2);
+        assert!(poll_time.max <= poll_time.total);
+    }
+}
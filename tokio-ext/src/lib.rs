@@ -1,8 +1,25 @@
+mod affinity;
+#[cfg(feature = "time")]
+pub mod future;
+#[cfg(any(feature = "rt", feature = "rt-multi-thread"))]
+mod runtime_config;
 #[cfg(any(feature = "rt", feature = "rt-multi-thread"))]
 mod runtime_flavor;
+#[cfg(all(feature = "rt", feature = "sync"))]
+mod runtime_monitor;
+#[cfg(all(feature = "rt", feature = "sync"))]
+mod runtimes;
 #[cfg(feature = "sync")]
 pub mod sync;
 pub mod task;
 
+pub use self::affinity::*;
 #[cfg(any(feature = "rt", feature = "rt-multi-thread"))]
-pub use self::runtime_flavor::*;
+pub use self::{runtime_config::*, runtime_flavor::*};
+#[cfg(all(feature = "rt", feature = "sync"))]
+pub use self::{runtime_monitor::*, runtimes::*};
+
+#[doc(hidden)]
+pub mod __private {
+    pub use tracing;
+}
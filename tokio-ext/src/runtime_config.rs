@@ -0,0 +1,306 @@
+use std::{
+    fmt::{self, Display},
+    io,
+    str::FromStr,
+    sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+    },
+};
+
+use tracing::warn;
+
+use crate::{ParseRuntimeFlavorError, RuntimeFlavor, pin_current_thread};
+
+pub type ThreadHook = Arc<dyn Fn() + Send + Sync>;
+
+/// Configuration of a Tokio runtime. Unset options use Tokio's defaults,
+/// except for the number of worker threads, which defaults to the available
+/// parallelism.
+///
+/// Parses from (and displays as) a comma separated list starting with the
+/// flavor followed by `key=value` options, e.g.
+/// `multi_thread,worker_threads=4,thread_name=io,cpu_cores=0-3`, which makes it
+/// usable as a command line argument. With the `serde` feature, it can also be
+/// read from config files.
+#[derive(Clone, Default)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Deserialize, serde::Serialize),
+    serde(default)
+)]
+pub struct RuntimeConfig {
+    pub flavor: RuntimeFlavor,
+    /// Ignored by the current thread flavor.
+    pub worker_threads: Option<usize>,
+    pub max_blocking_threads: Option<usize>,
+    /// Threads are named `{thread_name}-{index}`.
+    pub thread_name: Option<String>,
+    pub thread_stack_size: Option<usize>,
+    pub event_interval: Option<u32>,
+    pub global_queue_interval: Option<u32>,
+    /// CPU cores to pin the runtime threads to. Every thread, worker or
+    /// blocking, is pinned to the whole set, and the OS spreads them over it:
+    /// Tokio offers no way to tell a worker from a blocking thread when it
+    /// starts, so the cores cannot be assigned to the workers one by one.
+    /// Only supported on Linux.
+    ///
+    /// A current thread runtime has no worker threads; its cores only apply
+    /// when it is driven by [`Runtimes::start`], which pins the driving thread.
+    ///
+    /// [`Runtimes::start`]: crate::Runtimes::start
+    pub cpu_cores: Vec<usize>,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    on_thread_start: Option<ThreadHook>,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    on_thread_stop: Option<ThreadHook>,
+}
+
+impl RuntimeConfig {
+    #[must_use]
+    pub fn new(flavor: RuntimeFlavor) -> Self {
+        Self {
+            flavor,
+            ..Self::default()
+        }
+    }
+
+    /// Runs `hook` on every runtime thread after it starts.
+    #[must_use]
+    pub fn on_thread_start(mut self, hook: impl Fn() + Send + Sync + 'static) -> Self {
+        self.on_thread_start = Some(Arc::new(hook));
+        self
+    }
+
+    /// Runs `hook` on every runtime thread before it stops.
+    #[must_use]
+    pub fn on_thread_stop(mut self, hook: impl Fn() + Send + Sync + 'static) -> Self {
+        self.on_thread_stop = Some(Arc::new(hook));
+        self
+    }
+
+    /// Builds a runtime with all drivers enabled.
+    pub fn build(&self) -> io::Result<tokio::runtime::Runtime> {
+        let (mut builder, worker_threads) = match self.flavor {
+            #[cfg(feature = "rt")]
+            RuntimeFlavor::CurrentThread => (tokio::runtime::Builder::new_current_thread(), 0),
+            #[cfg(feature = "rt-multi-thread")]
+            RuntimeFlavor::MultiThread => {
+                let mut builder = tokio::runtime::Builder::new_multi_thread();
+                let worker_threads = self.worker_threads.unwrap_or_else(|| {
+                    std::thread::available_parallelism().map_or(1, std::num::NonZeroUsize::get)
+                });
+                builder.worker_threads(worker_threads);
+                (builder, worker_threads)
+            }
+        };
+        builder.enable_all();
+
+        if let Some(max_blocking_threads) = self.max_blocking_threads {
+            builder.max_blocking_threads(max_blocking_threads);
+        }
+        if let Some(thread_name) = self.thread_name.clone() {
+            let index = AtomicUsize::new(0);
+            builder.thread_name_fn(move || {
+                format!("{thread_name}-{}", index.fetch_add(1, Ordering::Relaxed))
+            });
+        }
+        if let Some(thread_stack_size) = self.thread_stack_size {
+            builder.thread_stack_size(thread_stack_size);
+        }
+        if let Some(event_interval) = self.event_interval {
+            builder.event_interval(event_interval);
+        }
+        if let Some(global_queue_interval) = self.global_queue_interval {
+            builder.global_queue_interval(global_queue_interval);
+        }
+        let cpu_cores = if worker_threads > 0 {
+            self.cpu_cores.clone()
+        } else {
+            Vec::new()
+        };
+        let on_thread_start = self.on_thread_start.clone();
+        if !cpu_cores.is_empty() || on_thread_start.is_some() {
+            builder.on_thread_start(move || {
+                if !cpu_cores.is_empty()
+                    && let Err(e) = pin_current_thread(&cpu_cores)
+                {
+                    warn!(
+                        cores = ?cpu_cores,
+                        error = %e,
+                        "Failed to pin runtime thread to CPU cores."
+                    );
+                }
+                if let Some(hook) = &on_thread_start {
+                    hook();
+                }
+            });
+        }
+        if let Some(hook) = self.on_thread_stop.clone() {
+            builder.on_thread_stop(move || hook());
+        }
+        builder.build()
+    }
+}
+
+impl fmt::Debug for RuntimeConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RuntimeConfig")
+            .field("flavor", &self.flavor)
+            .field("worker_threads", &self.worker_threads)
+            .field("max_blocking_threads", &self.max_blocking_threads)
+            .field("thread_name", &self.thread_name)
+            .field("thread_stack_size", &self.thread_stack_size)
+            .field("event_interval", &self.event_interval)
+            .field("global_queue_interval", &self.global_queue_interval)
+            .field("cpu_cores", &self.cpu_cores)
+            .field("on_thread_start", &self.on_thread_start.is_some())
+            .field("on_thread_stop", &self.on_thread_stop.is_some())
+            .finish()
+    }
+}
+
+impl Display for RuntimeConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.flavor)?;
+        let options = [
+            ("worker_threads", self.worker_threads.map(|v| v.to_string())),
+            (
+                "max_blocking_threads",
+                self.max_blocking_threads.map(|v| v.to_string()),
+            ),
+            ("thread_name", self.thread_name.clone()),
+            (
+                "thread_stack_size",
+                self.thread_stack_size.map(|v| v.to_string()),
+            ),
+            ("event_interval", self.event_interval.map(|v| v.to_string())),
+            (
+                "global_queue_interval",
+                self.global_queue_interval.map(|v| v.to_string()),
+            ),
+        ];
+        for (key, value) in options {
+            if let Some(value) = value {
+                write!(f, ",{key}={value}")?;
+            }
+        }
+        if !self.cpu_cores.is_empty() {
+            let cores = self
+                .cpu_cores
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>();
+            write!(f, ",cpu_cores={}", cores.join(":"))?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum ParseRuntimeConfigError {
+    #[error(transparent)]
+    Flavor(#[from] ParseRuntimeFlavorError),
+    #[error("Unknown runtime config option {0}.")]
+    UnknownOption(String),
+    #[error("Failed to parse runtime config option {0}.")]
+    Value(String),
+}
+
+impl FromStr for RuntimeConfig {
+    type Err = ParseRuntimeConfigError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.split(',').map(str::trim);
+        let mut config = Self::new(parts.next().unwrap_or_default().parse()?);
+        for part in parts {
+            let (key, value) = part
+                .split_once('=')
+                .ok_or_else(|| Self::Err::Value(part.to_owned()))?;
+            let value = value.trim();
+            let error = || Self::Err::Value(key.to_owned());
+            match key.trim() {
+                "worker_threads" => {
+                    config.worker_threads = Some(value.parse().map_err(|_| error())?)
+                }
+                "max_blocking_threads" => {
+                    config.max_blocking_threads = Some(value.parse().map_err(|_| error())?);
+                }
+                "thread_name" => config.thread_name = Some(value.to_owned()),
+                "thread_stack_size" => {
+                    config.thread_stack_size = Some(value.parse().map_err(|_| error())?);
+                }
+                "event_interval" => {
+                    config.event_interval = Some(value.parse().map_err(|_| error())?)
+                }
+                "global_queue_interval" => {
+                    config.global_queue_interval = Some(value.parse().map_err(|_| error())?);
+                }
+                "cpu_cores" => {
+                    config.cpu_cores = parse_cpu_cores(value).ok_or_else(error)?;
+                }
+                key => return Err(Self::Err::UnknownOption(key.to_owned())),
+            }
+        }
+        Ok(config)
+    }
+}
+
+// Parses colon separated cores and inclusive ranges, e.g. `0-3:6`.
+fn parse_cpu_cores(s: &str) -> Option<Vec<usize>> {
+    let mut cores = Vec::new();
+    for part in s.split(':') {
+        match part.split_once('-') {
+            Some((start, end)) => cores.extend(start.parse::<usize>().ok()?..=end.parse().ok()?),
+            None => cores.push(part.parse().ok()?),
+        }
+    }
+    Some(cores)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn from_str_round_trips() {
+        let s = "current_thread,max_blocking_threads=8,thread_name=market-data,event_interval=31";
+        let config = s.parse::<RuntimeConfig>().unwrap();
+        assert_eq!(config.max_blocking_threads, Some(8));
+        assert_eq!(config.thread_name.as_deref(), Some("market-data"));
+        assert_eq!(config.to_string(), s);
+
+        let config = "current_thread,cpu_cores=0-2:5"
+            .parse::<RuntimeConfig>()
+            .unwrap();
+        assert_eq!(config.cpu_cores, vec![0, 1, 2, 5]);
+        assert_eq!(config.to_string(), "current_thread,cpu_cores=0:1:2:5");
+
+        assert!(matches!(
+            "current_thread,workers=1".parse::<RuntimeConfig>(),
+            Err(ParseRuntimeConfigError::UnknownOption(_))
+        ));
+    }
+
+    #[test]
+    fn build_applies_thread_name_and_hooks() {
+        let started = Arc::new(AtomicUsize::new(0));
+        let mut config = RuntimeConfig::new(RuntimeFlavor::CurrentThread).on_thread_start({
+            let started = started.clone();
+            move || {
+                started.fetch_add(1, Ordering::Relaxed);
+            }
+        });
+        config.thread_name = Some("blocking".to_owned());
+        let runtime = config.build().unwrap();
+
+        let name = runtime
+            .block_on(async {
+                tokio::task::spawn_blocking(|| std::thread::current().name().map(str::to_owned))
+                    .await
+            })
+            .unwrap();
+        assert_eq!(name.as_deref(), Some("blocking-0"));
+        assert_eq!(started.load(Ordering::Relaxed), 1);
+    }
+}
@@ -1,6 +1,13 @@
 use std::{fmt::Display, io, str::FromStr};
 
+use crate::RuntimeConfig;
+
 #[derive(Clone, Debug)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Deserialize, serde::Serialize),
+    serde(rename_all = "snake_case")
+)]
 pub enum RuntimeFlavor {
     #[cfg(feature = "rt")]
     CurrentThread,
@@ -9,20 +16,19 @@ pub enum RuntimeFlavor {
 }
 
 impl RuntimeFlavor {
+    /// Builds a runtime with the default [`RuntimeConfig`] for this flavor.
     pub fn build(self) -> io::Result<tokio::runtime::Runtime> {
-        match self {
-            #[cfg(feature = "rt")]
-            Self::CurrentThread => tokio::runtime::Builder::new_current_thread()
-                .enable_all()
-                .build(),
-            #[cfg(feature = "rt-multi-thread")]
-            Self::MultiThread => tokio::runtime::Builder::new_multi_thread()
-                .worker_threads(
-                    std::thread::available_parallelism().map_or(1, std::num::NonZeroUsize::get),
-                )
-                .enable_all()
-                .build(),
-        }
+        RuntimeConfig::new(self).build()
+    }
+}
+
+/// Multi thread if enabled.
+impl Default for RuntimeFlavor {
+    fn default() -> Self {
+        #[cfg(feature = "rt-multi-thread")]
+        return Self::MultiThread;
+        #[cfg(not(feature = "rt-multi-thread"))]
+        return Self::CurrentThread;
     }
 }
 
@@ -0,0 +1,281 @@
+use std::{
+    io,
+    sync::{Arc, Mutex, PoisonError, mpsc},
+    thread,
+    time::{Duration, Instant},
+};
+
+use tokio::runtime::Handle;
+use tracing::warn;
+
+use crate::sync::LogThrottle;
+
+/// Watches a runtime for stalls, e.g. a blocking call on a worker thread.
+///
+/// A dedicated thread spawns a probe task onto the runtime every `interval`
+/// and measures how long the runtime takes to poll it. If the probe is not
+/// polled within `stall_threshold`, the monitor warns while the runtime is
+/// still stalled, and again with the total delay once it recovers. With
+/// `--cfg tokio_unstable`, warnings include worker busy time, queue depths and
+/// poll counts.
+///
+/// A current thread runtime only polls tasks while a thread drives it, e.g.
+/// in [`Runtime::block_on`](tokio::runtime::Runtime::block_on). While no
+/// thread does, e.g. between two `block_on` calls, the monitor reports a
+/// stall, as Tokio offers no way to tell an idle runtime from a blocked one.
+/// Monitor such runtimes only if they are driven continuously, e.g. by
+/// [`Runtimes::start`](crate::Runtimes::start).
+#[derive(Clone, Debug)]
+pub struct RuntimeMonitor {
+    name: String,
+    interval: Duration,
+    stall_threshold: Duration,
+}
+
+/// Latest measurements of a [`RuntimeMonitor`].
+#[derive(Clone, Debug, Default)]
+pub struct HealthReport {
+    pub probes: u64,
+    pub stalls: u64,
+    /// Scheduling delay of the latest probe.
+    pub last_delay: Duration,
+    pub max_delay: Duration,
+    #[cfg(tokio_unstable)]
+    pub metrics: RuntimeMetricsReport,
+}
+
+#[cfg(tokio_unstable)]
+#[derive(Clone, Debug, Default)]
+pub struct RuntimeMetricsReport {
+    pub global_queue_depth: usize,
+    pub workers: Vec<WorkerMetricsReport>,
+}
+
+#[cfg(tokio_unstable)]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct WorkerMetricsReport {
+    pub busy: Duration,
+    pub local_queue_depth: usize,
+    pub polls: u64,
+}
+
+impl RuntimeMonitor {
+    #[must_use]
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            interval: Duration::from_secs(1),
+            stall_threshold: Duration::from_millis(100),
+        }
+    }
+
+    /// Sets the time between probes. Defaults to 1 second.
+    #[must_use]
+    pub fn interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// Sets the scheduling delay past which the runtime counts as stalled.
+    /// Defaults to 100 milliseconds.
+    #[must_use]
+    pub fn stall_threshold(mut self, stall_threshold: Duration) -> Self {
+        self.stall_threshold = stall_threshold;
+        self
+    }
+
+    /// Starts monitoring the runtime of `handle` on a thread named after the
+    /// monitor. The thread stops when the returned handle is dropped or the
+    /// runtime shuts down.
+    pub fn start(self, handle: Handle) -> io::Result<MonitorHandle> {
+        let report = Arc::new(Mutex::new(HealthReport::default()));
+        let (stop, stop_rx) = mpsc::channel();
+        let thread = thread::Builder::new().name(self.name.clone()).spawn({
+            let report = report.clone();
+            move || self.run(&handle, &report, &stop_rx)
+        })?;
+        Ok(MonitorHandle {
+            report,
+            stop: Some(stop),
+            thread: Some(thread),
+        })
+    }
+
+    fn run(&self, handle: &Handle, report: &Mutex<HealthReport>, stop: &mpsc::Receiver<()>) {
+        // Recoveries are only logged for logged stalls, so they need no
+        // throttle of their own.
+        let throttle = LogThrottle::default();
+        loop {
+            let (tx, rx) = mpsc::channel();
+            let sent = Instant::now();
+            handle.spawn(async move {
+                let _ = tx.send(sent.elapsed());
+            });
+
+            let mut stalled = false;
+            let mut stall_logged = false;
+            let delay = loop {
+                let timeout = if stalled {
+                    self.interval
+                } else {
+                    self.stall_threshold
+                };
+                match rx.recv_timeout(timeout) {
+                    Ok(delay) => break delay,
+                    // The runtime shut down and dropped the probe.
+                    Err(mpsc::RecvTimeoutError::Disconnected) => return,
+                    Err(mpsc::RecvTimeoutError::Timeout) => {}
+                }
+                if !matches!(stop.try_recv(), Err(mpsc::TryRecvError::Empty)) {
+                    return;
+                }
+                if !stalled {
+                    stalled = true;
+                    if let Some(suppressed) = throttle.check() {
+                        stall_logged = true;
+                        warn!(
+                            runtime = self.name,
+                            threshold = ?self.stall_threshold,
+                            suppressed = suppressed,
+                            metrics = metrics_field(handle),
+                            "Runtime stalled."
+                        );
+                    }
+                }
+            };
+            if stall_logged {
+                warn!(
+                    runtime = self.name,
+                    delay = ?delay,
+                    metrics = metrics_field(handle),
+                    "Runtime recovered from stall."
+                );
+            }
+
+            {
+                let mut report = report.lock().unwrap_or_else(PoisonError::into_inner);
+                report.probes += 1;
+                report.stalls += u64::from(stalled);
+                report.last_delay = delay;
+                report.max_delay = report.max_delay.max(delay);
+                #[cfg(tokio_unstable)]
+                {
+                    report.metrics = metrics(handle);
+                }
+            }
+
+            match stop.recv_timeout(self.interval) {
+                Err(mpsc::RecvTimeoutError::Timeout) => {}
+                _ => return,
+            }
+        }
+    }
+}
+
+#[cfg(tokio_unstable)]
+fn metrics(handle: &Handle) -> RuntimeMetricsReport {
+    let metrics = handle.metrics();
+    RuntimeMetricsReport {
+        global_queue_depth: metrics.global_queue_depth(),
+        workers: (0..metrics.num_workers())
+            .map(|worker| WorkerMetricsReport {
+                busy: metrics.worker_total_busy_duration(worker),
+                local_queue_depth: metrics.worker_local_queue_depth(worker),
+                polls: metrics.worker_poll_count(worker),
+            })
+            .collect(),
+    }
+}
+
+#[cfg(tokio_unstable)]
+fn metrics_field(handle: &Handle) -> impl tracing::Value {
+    tracing::field::debug(metrics(handle))
+}
+
+#[cfg(not(tokio_unstable))]
+fn metrics_field(_handle: &Handle) -> impl tracing::Value {
+    tracing::field::Empty
+}
+
+/// Handle to a started [`RuntimeMonitor`]. Dropping it stops the monitor.
+pub struct MonitorHandle {
+    report: Arc<Mutex<HealthReport>>,
+    stop: Option<mpsc::Sender<()>>,
+    thread: Option<thread::JoinHandle<()>>,
+}
+
+impl MonitorHandle {
+    #[must_use]
+    pub fn report(&self) -> HealthReport {
+        self.report
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+}
+
+impl Drop for MonitorHandle {
+    fn drop(&mut self) {
+        drop(self.stop.take());
+        if let Some(thread) = self.thread.take()
+            && thread.join().is_err()
+        {
+            warn!("Runtime monitor thread panicked.");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detects_blocking_call_on_runtime() {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        let monitor = RuntimeMonitor::new("monitor")
+            .interval(Duration::from_millis(10))
+            .stall_threshold(Duration::from_millis(50))
+            .start(runtime.handle().clone())
+            .unwrap();
+
+        runtime.block_on(async {
+            tokio::task::yield_now().await;
+            thread::sleep(Duration::from_millis(200));
+            tokio::task::yield_now().await;
+        });
+        // The monitor records the delayed probe shortly after it ran.
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while monitor.report().stalls == 0 && Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(10));
+        }
+        let report = monitor.report();
+        assert!(report.stalls >= 1);
+        assert!(report.max_delay >= Duration::from_millis(50));
+    }
+
+    #[test]
+    fn reports_undriven_current_thread_runtime_as_stalled() {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        let monitor = RuntimeMonitor::new("monitor")
+            .interval(Duration::from_millis(10))
+            .stall_threshold(Duration::from_millis(50))
+            .start(runtime.handle().clone())
+            .unwrap();
+
+        // Nothing polls the probe until the runtime is driven again.
+        thread::sleep(Duration::from_millis(200));
+        assert_eq!(monitor.report().probes, 0);
+        runtime.block_on(tokio::task::yield_now());
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while monitor.report().probes == 0 && Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(10));
+        }
+        let report = monitor.report();
+        assert_eq!(report.stalls, 1);
+        assert!(report.max_delay >= Duration::from_millis(200));
+    }
+}
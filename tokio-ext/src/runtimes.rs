@@ -0,0 +1,136 @@
+use std::{io, thread};
+
+use tokio::{runtime::Handle, sync::oneshot, task::JoinHandle};
+use tracing::warn;
+
+use crate::{RuntimeConfig, RuntimeFlavor, pin_current_thread};
+
+/// Several named runtimes, each driven by a dedicated thread, e.g. a current
+/// thread runtime pinned to an isolated core for a latency-sensitive path next
+/// to a multi thread runtime for everything else.
+///
+/// Tasks can be spawned onto any runtime from anywhere through its
+/// [`Handle`]. Dropping `Runtimes` shuts all runtimes down.
+#[derive(Default)]
+pub struct Runtimes {
+    runtimes: Vec<NamedRuntime>,
+}
+
+struct NamedRuntime {
+    name: String,
+    handle: Handle,
+    shutdown: Option<oneshot::Sender<()>>,
+    thread: Option<thread::JoinHandle<()>>,
+}
+
+impl Runtimes {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Builds and starts a runtime. The thread driving a current thread
+    /// runtime is named after it and pinned to its CPU cores, if any.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a runtime with the same name was already started.
+    pub fn start(
+        &mut self,
+        name: impl Into<String>,
+        config: &RuntimeConfig,
+    ) -> io::Result<&Handle> {
+        let name = name.into();
+        assert!(self.get(&name).is_none(), "runtime {name} already started");
+
+        let runtime = config.build()?;
+        let handle = runtime.handle().clone();
+        let (shutdown, shutdown_rx) = oneshot::channel::<()>();
+        let cpu_cores = match config.flavor {
+            RuntimeFlavor::CurrentThread => config.cpu_cores.clone(),
+            #[cfg(feature = "rt-multi-thread")]
+            // Worker threads are pinned by the runtime itself.
+            RuntimeFlavor::MultiThread => Vec::new(),
+        };
+        let thread = thread::Builder::new().name(name.clone()).spawn(move || {
+            if !cpu_cores.is_empty()
+                && let Err(e) = pin_current_thread(&cpu_cores)
+            {
+                warn!(error = %e, "Failed to pin runtime thread to CPU cores.");
+            }
+            // Resolves when the sender is sent to or dropped.
+            let _ = runtime.block_on(shutdown_rx);
+        })?;
+
+        self.runtimes.push(NamedRuntime {
+            name,
+            handle,
+            shutdown: Some(shutdown),
+            thread: Some(thread),
+        });
+        Ok(&self.runtimes[self.runtimes.len() - 1].handle)
+    }
+
+    #[must_use]
+    pub fn get(&self, name: &str) -> Option<&Handle> {
+        self.runtimes
+            .iter()
+            .find(|runtime| runtime.name == name)
+            .map(|runtime| &runtime.handle)
+    }
+
+    /// Spawns a task onto the named runtime, or returns `None` if there is no
+    /// such runtime.
+    pub fn spawn_on<F>(&self, name: &str, future: F) -> Option<JoinHandle<F::Output>>
+    where
+        F: Future + Send + 'static,
+        F::Output: Send + 'static,
+    {
+        Some(self.get(name)?.spawn(future))
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.runtimes.iter().map(|runtime| runtime.name.as_str())
+    }
+}
+
+impl Drop for Runtimes {
+    fn drop(&mut self) {
+        for runtime in &mut self.runtimes {
+            if let Some(shutdown) = runtime.shutdown.take() {
+                let _ = shutdown.send(());
+            }
+        }
+        for runtime in &mut self.runtimes {
+            if let Some(thread) = runtime.thread.take()
+                && thread.join().is_err()
+            {
+                warn!(runtime = runtime.name, "Runtime thread panicked.");
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn spawn_on_runs_on_named_runtime() {
+        let mut runtimes = Runtimes::new();
+        runtimes
+            .start("hot", &RuntimeConfig::new(RuntimeFlavor::CurrentThread))
+            .unwrap();
+
+        let thread_name = runtimes
+            .spawn_on("hot", async { thread::current().name().map(str::to_owned) })
+            .unwrap();
+        let thread_name = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap()
+            .block_on(thread_name)
+            .unwrap();
+        assert_eq!(thread_name.as_deref(), Some("hot"));
+        assert!(runtimes.spawn_on("cold", async {}).is_none());
+    }
+}
@@ -0,0 +1,148 @@
+use std::{
+    sync::{Mutex, MutexGuard, PoisonError},
+    time::Duration,
+};
+
+use tokio::{
+    sync::watch,
+    time::{error::Elapsed, timeout},
+};
+
+/// Like [`tokio::sync::Barrier`], but cancel safe: a waiter which is dropped
+/// before the barrier is released no longer counts as arrived. This makes
+/// [`Self::wait_timeout`] possible.
+///
+/// The barrier is reusable; once `n` tasks arrived, it is released and starts
+/// counting again.
+pub struct Barrier {
+    n: usize,
+    state: Mutex<State>,
+    released: watch::Sender<u64>,
+}
+
+struct State {
+    arrived: usize,
+    generation: u64,
+}
+
+/// Returned by [`Barrier::wait`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BarrierWaitResult {
+    leader: bool,
+}
+
+impl BarrierWaitResult {
+    /// Whether this task released the barrier. Exactly one task per release
+    /// is the leader.
+    #[must_use]
+    pub fn is_leader(&self) -> bool {
+        self.leader
+    }
+}
+
+impl Barrier {
+    /// # Panics
+    ///
+    /// Panics if `n` is 0.
+    #[must_use]
+    pub fn new(n: usize) -> Self {
+        assert!(n > 0, "barrier requires n > 0");
+        Self {
+            n,
+            state: Mutex::new(State {
+                arrived: 0,
+                generation: 0,
+            }),
+            released: watch::Sender::new(0),
+        }
+    }
+
+    /// Number of tasks currently waiting.
+    #[must_use]
+    pub fn waiting(&self) -> usize {
+        self.state().arrived
+    }
+
+    /// Waits until `n` tasks are waiting. Cancel safe.
+    pub async fn wait(&self) -> BarrierWaitResult {
+        let (generation, mut released) = {
+            let mut state = self.state();
+            state.arrived += 1;
+            if state.arrived == self.n {
+                state.arrived = 0;
+                state.generation += 1;
+                self.released.send_replace(state.generation);
+                return BarrierWaitResult { leader: true };
+            }
+            (state.generation, self.released.subscribe())
+        };
+
+        let mut arrival = Arrival {
+            barrier: self,
+            generation,
+            released: false,
+        };
+        released
+            .wait_for(|released| *released > generation)
+            .await
+            .expect("sender is owned by the barrier");
+        arrival.released = true;
+        BarrierWaitResult { leader: false }
+    }
+
+    /// Like [`Self::wait`], but gives up after `duration`, withdrawing the
+    /// arrival.
+    pub async fn wait_timeout(&self, duration: Duration) -> Result<BarrierWaitResult, Elapsed> {
+        timeout(duration, self.wait()).await
+    }
+
+    fn state(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+// Withdraws the arrival of a cancelled waiter.
+struct Arrival<'a> {
+    barrier: &'a Barrier,
+    generation: u64,
+    released: bool,
+}
+
+impl Drop for Arrival<'_> {
+    fn drop(&mut self) {
+        if self.released {
+            return;
+        }
+        let mut state = self.barrier.state();
+        // The barrier may have been released after the waiter was last polled.
+        if state.generation == self.generation {
+            state.arrived -= 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+
+    #[tokio::test(start_paused = true)]
+    async fn wait_timeout_withdraws_arrival() {
+        let barrier = Arc::new(Barrier::new(2));
+        assert!(barrier.wait_timeout(Duration::from_secs(1)).await.is_err());
+        assert_eq!(barrier.waiting(), 0);
+
+        // Reusable after a release.
+        for _ in 0..2 {
+            let waiter = tokio::spawn({
+                let barrier = barrier.clone();
+                async move { barrier.wait().await }
+            });
+            tokio::task::yield_now().await;
+            assert_eq!(barrier.waiting(), 1);
+            assert!(barrier.wait().await.is_leader());
+            assert!(!waiter.await.unwrap().is_leader());
+        }
+    }
+}
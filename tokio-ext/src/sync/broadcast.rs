@@ -1,29 +1,40 @@
-use std::{ops::Deref, sync::atomic::AtomicU64};
+use std::{
+    ops::{Deref, DerefMut},
+    sync::Arc,
+};
 
-use tokio::sync::broadcast::error::SendError;
+use futures_util::future::BoxFuture;
+use tokio::sync::broadcast::error::{RecvError, SendError};
 use tracing::warn;
 
-use super::log_throttle::should_log;
+use super::{
+    log_throttle::LogThrottle,
+    metrics::{ChannelMetrics, ChannelMetricsSnapshot},
+};
 
 #[must_use]
-pub fn channel<T: Clone>(capacity: usize) -> (Sender<T>, tokio::sync::broadcast::Receiver<T>) {
+pub fn channel<T: Clone>(name: impl Into<String>, capacity: usize) -> (Sender<T>, Receiver<T>) {
+    let name: Arc<str> = name.into().into();
     let (tx, rx) = tokio::sync::broadcast::channel(capacity);
     (
         Sender {
+            name: name.clone(),
             capacity,
             tx,
-            next_log_ms: AtomicU64::new(0),
+            log_throttle: LogThrottle::default(),
+            metrics: ChannelMetrics::default(),
         },
-        rx,
+        Receiver::new(name, rx),
     )
 }
 
-pub use tokio::sync::broadcast::Receiver;
-
 pub struct Sender<T> {
+    // Shared with the receivers, which log it on lag.
+    name: Arc<str>,
     capacity: usize,
     tx: tokio::sync::broadcast::Sender<T>,
-    next_log_ms: AtomicU64,
+    log_throttle: LogThrottle,
+    metrics: ChannelMetrics,
 }
 
 impl<T> Deref for Sender<T> {
@@ -35,6 +46,27 @@ impl<T> Deref for Sender<T> {
 }
 
 impl<T> Sender<T> {
+    /// Creates a new [`Receiver`] with the default [`LagPolicy::Error`].
+    #[must_use]
+    pub fn subscribe(&self) -> Receiver<T> {
+        Receiver::new(self.name.clone(), self.tx.subscribe())
+    }
+
+    #[must_use]
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    #[must_use]
+    pub fn metrics(&self) -> ChannelMetricsSnapshot {
+        self.metrics.snapshot(self.len(), self.capacity)
+    }
+
+    /// Emits the channel metrics as fields of a [`tracing::info!`] event.
+    pub fn log_metrics(&self) {
+        self.metrics().log(&self.name);
+    }
+
     /// Sends a value, logging on high channel usage. To keep the logging off
     /// the hot path, the warning is emitted at most once per second per
     /// channel.
@@ -42,13 +74,186 @@ impl<T> Sender<T> {
     pub fn send_log_backpressure(&self, value: T) -> Result<usize, SendError<T>> {
         let capacity = self.capacity;
         let len = self.len();
-        if len > capacity / 2 && should_log(&self.next_log_ms) {
+        self.metrics.record_len(len, capacity);
+        // Once the channel is full, every send overwrites the oldest value for
+        // receivers that have yet to see it.
+        if len >= capacity {
+            self.metrics.record_dropped();
+        }
+        if len > capacity / 2
+            && let Some(suppressed) = self.log_throttle.check()
+        {
             warn!(
+                channel = &*self.name,
                 len = len,
                 capacity = capacity,
+                suppressed = suppressed,
                 "High channel usage in broadcast send."
             );
         }
-        self.send(value)
+        let result = self.send(value);
+        match result {
+            Ok(_) => self.metrics.record_sent(),
+            Err(_) => self.metrics.record_closed(),
+        }
+        result
+    }
+}
+
+/// What a [`Receiver`] does after it lagged behind the sender and missed
+/// messages.
+pub enum LagPolicy<T> {
+    /// Continue with the oldest message still retained by the channel.
+    Continue,
+    /// Return [`RecvError::Lagged`] with the number of missed messages.
+    Error,
+    /// Call the callback with the number of missed messages and return the
+    /// value it resolves to, e.g. a snapshot fetched from elsewhere. If it
+    /// resolves to `None`, continue as with [`LagPolicy::Continue`].
+    Resync(ResyncFn<T>),
+}
+
+pub type ResyncFn<T> = Box<dyn FnMut(u64) -> BoxFuture<'static, Option<T>> + Send>;
+
+/// A [`tokio::sync::broadcast::Receiver`] that logs when it lags behind the
+/// sender and handles the lag according to a [`LagPolicy`].
+pub struct Receiver<T> {
+    name: Arc<str>,
+    rx: tokio::sync::broadcast::Receiver<T>,
+    lag_policy: LagPolicy<T>,
+    // A resync interrupted by cancelling `recv`, resumed by the next call.
+    resync: Option<BoxFuture<'static, Option<T>>>,
+    skipped: u64,
+    log_throttle: LogThrottle,
+}
+
+impl<T> Deref for Receiver<T> {
+    type Target = tokio::sync::broadcast::Receiver<T>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.rx
+    }
+}
+
+impl<T> DerefMut for Receiver<T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.rx
+    }
+}
+
+impl<T> Receiver<T> {
+    /// Wraps a Tokio receiver, naming it `name` in its warnings.
+    #[must_use]
+    pub fn new(name: impl Into<Arc<str>>, rx: tokio::sync::broadcast::Receiver<T>) -> Self {
+        Self {
+            name: name.into(),
+            rx,
+            lag_policy: LagPolicy::Error,
+            resync: None,
+            skipped: 0,
+            log_throttle: LogThrottle::default(),
+        }
+    }
+
+    #[must_use]
+    pub fn with_lag_policy(mut self, lag_policy: LagPolicy<T>) -> Self {
+        self.lag_policy = lag_policy;
+        self
+    }
+
+    #[must_use]
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Total number of messages missed due to lagging.
+    #[must_use]
+    pub fn skipped(&self) -> u64 {
+        self.skipped
+    }
+
+    /// Receives the next message, logging on lag. To keep the logging off the
+    /// hot path, the warning is emitted at most once per second per receiver.
+    ///
+    /// Cancel safe: a resync in progress is resumed by the next call.
+    pub async fn recv(&mut self) -> Result<T, RecvError>
+    where
+        T: Clone,
+    {
+        loop {
+            if let Some(resync) = &mut self.resync {
+                let value = resync.await;
+                self.resync = None;
+                if let Some(value) = value {
+                    return Ok(value);
+                }
+            }
+            match self.rx.recv().await {
+                Err(RecvError::Lagged(skipped)) => {
+                    self.skipped += skipped;
+                    if let Some(suppressed) = self.log_throttle.check() {
+                        warn!(
+                            channel = &*self.name,
+                            skipped = skipped,
+                            total_skipped = self.skipped,
+                            suppressed = suppressed,
+                            "Lagged in broadcast receive."
+                        );
+                    }
+                    match &mut self.lag_policy {
+                        LagPolicy::Continue => {}
+                        LagPolicy::Error => return Err(RecvError::Lagged(skipped)),
+                        LagPolicy::Resync(resync) => self.resync = Some(resync(skipped)),
+                    }
+                }
+                result => return result,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use tokio::time::{sleep, timeout};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn recv_resyncs_on_lag() {
+        let (tx, rx) = channel("test", 2);
+        let mut rx = rx.with_lag_policy(LagPolicy::Resync(Box::new(|skipped| {
+            Box::pin(async move { Some(skipped * 100) })
+        })));
+        for i in 0..4 {
+            tx.send_log_backpressure(i).unwrap();
+        }
+
+        assert_eq!(rx.name(), "test");
+        assert_eq!(tx.subscribe().name(), "test");
+        assert_eq!(rx.recv().await.unwrap(), 200);
+        assert_eq!(rx.skipped(), 2);
+        assert_eq!(rx.recv().await.unwrap(), 2);
+        assert_eq!(rx.recv().await.unwrap(), 3);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn recv_resumes_cancelled_resync() {
+        let (tx, rx) = channel("test", 1);
+        let mut rx = rx.with_lag_policy(LagPolicy::Resync(Box::new(|skipped| {
+            Box::pin(async move {
+                sleep(Duration::from_secs(10)).await;
+                Some(skipped * 100)
+            })
+        })));
+        for i in 0..2 {
+            tx.send_log_backpressure(i).unwrap();
+        }
+
+        let cancelled = timeout(Duration::from_secs(1), rx.recv()).await;
+        assert!(cancelled.is_err());
+        assert_eq!(rx.recv().await.unwrap(), 100);
+        assert_eq!(rx.recv().await.unwrap(), 1);
     }
 }
@@ -0,0 +1,313 @@
+use std::{
+    collections::{HashMap, hash_map},
+    hash::Hash,
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+};
+
+use tokio::sync::{
+    broadcast::error::SendError,
+    watch::{self, error::RecvError},
+};
+use tracing::warn;
+
+use super::{
+    log_throttle::LogThrottle,
+    metrics::{ChannelMetrics, ChannelMetricsSnapshot},
+};
+
+/// Creates a channel which keeps only the latest value per key, e.g. the
+/// latest quote per symbol.
+///
+/// Unlike a broadcast channel, a slow receiver never lags: values it did not
+/// get to see are overwritten (conflated) by newer ones for the same key, and
+/// it always observes a consistent snapshot of the latest values.
+#[must_use]
+pub fn channel<K, V>(name: impl Into<String>) -> (Sender<K, V>, Receiver<K, V>) {
+    let (tx, rx) = watch::channel(State {
+        version: 0,
+        entries: HashMap::new(),
+    });
+    let metrics = Arc::new(ChannelMetrics::default());
+    (
+        Sender {
+            name: name.into(),
+            tx,
+            log_throttle: LogThrottle::default(),
+            metrics: metrics.clone(),
+        },
+        Receiver {
+            rx,
+            seen_version: 0,
+            conflated: 0,
+            metrics,
+        },
+    )
+}
+
+struct State<K, V> {
+    // Incremented on every send.
+    version: u64,
+    entries: HashMap<K, Entry<V>>,
+}
+
+struct Entry<V> {
+    value: V,
+    // State version at which the value was sent.
+    version: u64,
+    // Whether any receiver has seen the value.
+    observed: AtomicBool,
+}
+
+pub struct Sender<K, V> {
+    name: String,
+    tx: watch::Sender<State<K, V>>,
+    log_throttle: LogThrottle,
+    // Shared with the receivers, which record the received values.
+    metrics: Arc<ChannelMetrics>,
+}
+
+impl<K, V> Sender<K, V>
+where
+    K: Eq + Hash,
+{
+    #[must_use]
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the channel metrics. Conflated values count as dropped. The
+    /// channel is unbounded, so `len` is the number of keys and `capacity` is
+    /// 0.
+    #[must_use]
+    pub fn metrics(&self) -> ChannelMetricsSnapshot {
+        self.metrics.snapshot(self.tx.borrow().entries.len(), 0)
+    }
+
+    /// Emits the channel metrics as fields of a [`tracing::info!`] event.
+    pub fn log_metrics(&self) {
+        self.metrics().log(&self.name);
+    }
+
+    /// Sets the latest value for a key, logging when it overwrites a value
+    /// that no receiver has seen yet. To keep the logging off the hot path,
+    /// the warning is emitted at most once per second per channel.
+    ///
+    /// Fails if there are no receivers.
+    #[track_caller]
+    pub fn send_log_backpressure(&self, key: K, value: V) -> Result<(), SendError<(K, V)>> {
+        if self.tx.receiver_count() == 0 {
+            self.metrics.record_closed();
+            return Err(SendError((key, value)));
+        }
+
+        let mut conflated = false;
+        self.tx.send_modify(|state| {
+            state.version += 1;
+            let version = state.version;
+            match state.entries.entry(key) {
+                hash_map::Entry::Occupied(mut occupied) => {
+                    let entry = occupied.get_mut();
+                    conflated = !*entry.observed.get_mut();
+                    *entry = Entry {
+                        value,
+                        version,
+                        observed: AtomicBool::new(false),
+                    };
+                }
+                hash_map::Entry::Vacant(vacant) => {
+                    vacant.insert(Entry {
+                        value,
+                        version,
+                        observed: AtomicBool::new(false),
+                    });
+                }
+            }
+        });
+
+        self.metrics.record_sent();
+        if conflated {
+            self.metrics.record_dropped();
+        }
+        if conflated && let Some(suppressed) = self.log_throttle.check() {
+            warn!(
+                channel = self.name,
+                conflated = self.metrics.dropped(),
+                suppressed = suppressed,
+                "Conflated unobserved value in conflating send."
+            );
+        }
+        Ok(())
+    }
+
+    /// Removes a key, e.g. when a symbol is unsubscribed. Removals do not wake
+    /// the receivers and are not reported by [`Receiver::changed`], only
+    /// reflected in [`Receiver::snapshot`].
+    pub fn remove(&self, key: &K) -> bool {
+        let mut removed = false;
+        self.tx.send_if_modified(|state| {
+            removed = state.entries.remove(key).is_some();
+            false
+        });
+        removed
+    }
+
+    #[must_use]
+    pub fn subscribe(&self) -> Receiver<K, V> {
+        let rx = self.tx.subscribe();
+        let seen_version = rx.borrow().version;
+        Receiver {
+            rx,
+            seen_version,
+            conflated: 0,
+            metrics: self.metrics.clone(),
+        }
+    }
+
+    #[must_use]
+    pub fn receiver_count(&self) -> usize {
+        self.tx.receiver_count()
+    }
+
+    /// Number of values overwritten before any receiver saw them.
+    #[must_use]
+    pub fn conflated(&self) -> u64 {
+        self.metrics.dropped()
+    }
+}
+
+pub struct Receiver<K, V> {
+    rx: watch::Receiver<State<K, V>>,
+    seen_version: u64,
+    conflated: u64,
+    metrics: Arc<ChannelMetrics>,
+}
+
+impl<K, V> Clone for Receiver<K, V> {
+    fn clone(&self) -> Self {
+        Self {
+            rx: self.rx.clone(),
+            seen_version: self.seen_version,
+            conflated: self.conflated,
+            metrics: self.metrics.clone(),
+        }
+    }
+}
+
+impl<K, V> Receiver<K, V>
+where
+    K: Eq + Hash + Clone,
+    V: Clone,
+{
+    /// Waits for values to change and returns the latest value of every key
+    /// sent since the previous call, oldest first.
+    ///
+    /// A receiver created by [`Sender::subscribe`] only sees keys sent after
+    /// subscribing; use [`Self::snapshot`] to get the values sent before.
+    ///
+    /// Fails once the sender has been dropped.
+    pub async fn changed(&mut self) -> Result<Vec<(K, V)>, RecvError> {
+        loop {
+            self.rx.changed().await?;
+            let changes = self.take_changes();
+            // All sends since the previous call may be for keys removed since.
+            if !changes.is_empty() {
+                self.metrics.record_received(changes.len() as u64);
+                return Ok(changes);
+            }
+        }
+    }
+
+    fn take_changes(&mut self) -> Vec<(K, V)> {
+        let state = self.rx.borrow_and_update();
+        let mut changes = state
+            .entries
+            .iter()
+            .filter(|(_, entry)| entry.version > self.seen_version)
+            .map(|(key, entry)| {
+                entry.observed.store(true, Ordering::Relaxed);
+                (entry.version, key.clone(), entry.value.clone())
+            })
+            .collect::<Vec<_>>();
+        changes.sort_unstable_by_key(|(version, _, _)| *version);
+
+        // Every send bumps the version by one, so any send not reflected in
+        // the changes was conflated (or removed) before this receiver saw it.
+        let sends = state.version - self.seen_version;
+        self.conflated += sends.saturating_sub(changes.len() as u64);
+        self.seen_version = state.version;
+
+        changes
+            .into_iter()
+            .map(|(_, key, value)| (key, value))
+            .collect()
+    }
+
+    /// Returns the latest value of every key.
+    #[must_use]
+    pub fn snapshot(&self) -> HashMap<K, V> {
+        self.rx
+            .borrow()
+            .entries
+            .iter()
+            .map(|(key, entry)| {
+                entry.observed.store(true, Ordering::Relaxed);
+                (key.clone(), entry.value.clone())
+            })
+            .collect()
+    }
+
+    #[must_use]
+    pub fn get(&self, key: &K) -> Option<V> {
+        self.rx.borrow().entries.get(key).map(|entry| {
+            entry.observed.store(true, Ordering::Relaxed);
+            entry.value.clone()
+        })
+    }
+
+    /// Number of values this receiver missed because they were overwritten
+    /// before it called [`Self::changed`].
+    #[must_use]
+    pub fn conflated(&self) -> u64 {
+        self.conflated
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn changed_returns_latest_value_per_key() {
+        let (tx, mut rx) = channel("test");
+        tx.send_log_backpressure("a", 1).unwrap();
+        tx.send_log_backpressure("b", 1).unwrap();
+        tx.send_log_backpressure("a", 2).unwrap();
+
+        assert_eq!(rx.changed().await.unwrap(), vec![("b", 1), ("a", 2)]);
+        assert_eq!(rx.conflated(), 1);
+        assert_eq!(tx.conflated(), 1);
+        let metrics = tx.metrics();
+        assert_eq!((metrics.sent, metrics.received), (3, 2));
+
+        drop(tx);
+        assert!(rx.changed().await.is_err());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn changed_skips_removed_keys() {
+        let (tx, mut rx) = channel("test");
+        tx.send_log_backpressure("a", 1).unwrap();
+        assert!(tx.remove(&"a"));
+        assert!(!tx.remove(&"a"));
+
+        let changed = tokio::spawn(async move { rx.changed().await });
+        tokio::task::yield_now().await;
+        assert!(!changed.is_finished());
+
+        tx.send_log_backpressure("b", 1).unwrap();
+        assert_eq!(changed.await.unwrap().unwrap(), vec![("b", 1)]);
+    }
+}
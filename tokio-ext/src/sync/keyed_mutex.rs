@@ -0,0 +1,160 @@
+use std::{
+    collections::HashMap,
+    hash::Hash,
+    sync::{Arc, Mutex, MutexGuard, PoisonError},
+};
+
+use tokio::sync::OwnedMutexGuard;
+
+/// Serialises work per key, e.g. requests for the same account, while work for
+/// different keys runs concurrently.
+///
+/// A key only takes up memory while it is locked or waited for.
+pub struct KeyedMutex<K> {
+    locks: Mutex<HashMap<K, Lock>>,
+}
+
+struct Lock {
+    mutex: Arc<tokio::sync::Mutex<()>>,
+    // Number of guards held or waited for.
+    users: usize,
+}
+
+impl<K> Default for KeyedMutex<K> {
+    fn default() -> Self {
+        Self {
+            locks: Mutex::default(),
+        }
+    }
+}
+
+impl<K> KeyedMutex<K>
+where
+    K: Eq + Hash + Clone,
+{
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Locks the key, waiting for the current holder to release it. Waiters
+    /// acquire the lock in the order they called `lock`.
+    ///
+    /// Cancel safe: a cancelled waiter gives up its place in the queue.
+    pub async fn lock(&self, key: K) -> KeyedMutexGuard<'_, K> {
+        let mutex = {
+            let mut locks = self.locks();
+            let lock = locks.entry(key.clone()).or_insert_with(|| Lock {
+                mutex: Arc::default(),
+                users: 0,
+            });
+            lock.users += 1;
+            lock.mutex.clone()
+        };
+
+        // Created before waiting so that a cancelled waiter is accounted for.
+        let mut guard = KeyedMutexGuard {
+            keyed_mutex: self,
+            key,
+            guard: None,
+        };
+        guard.guard = Some(mutex.lock_owned().await);
+        guard
+    }
+
+    /// Locks the key if it is not locked or waited for.
+    #[must_use]
+    pub fn try_lock(&self, key: K) -> Option<KeyedMutexGuard<'_, K>> {
+        let mut locks = self.locks();
+        if locks.contains_key(&key) {
+            return None;
+        }
+        let mutex = Arc::new(tokio::sync::Mutex::new(()));
+        let guard = mutex.clone().try_lock_owned().ok()?;
+        locks.insert(key.clone(), Lock { mutex, users: 1 });
+        Some(KeyedMutexGuard {
+            keyed_mutex: self,
+            key,
+            guard: Some(guard),
+        })
+    }
+
+    /// Number of keys locked or waited for.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.locks().len()
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl<K> KeyedMutex<K> {
+    fn locks(&self) -> MutexGuard<'_, HashMap<K, Lock>> {
+        self.locks.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+/// Unlocks the key when dropped.
+pub struct KeyedMutexGuard<'a, K>
+where
+    K: Eq + Hash,
+{
+    keyed_mutex: &'a KeyedMutex<K>,
+    key: K,
+    // `None` while waiting for the lock.
+    guard: Option<OwnedMutexGuard<()>>,
+}
+
+impl<K> KeyedMutexGuard<'_, K>
+where
+    K: Eq + Hash,
+{
+    #[must_use]
+    pub fn key(&self) -> &K {
+        &self.key
+    }
+}
+
+impl<K> Drop for KeyedMutexGuard<'_, K>
+where
+    K: Eq + Hash,
+{
+    fn drop(&mut self) {
+        drop(self.guard.take());
+        let mut locks = self.keyed_mutex.locks();
+        if let Some(lock) = locks.get_mut(&self.key) {
+            lock.users -= 1;
+            if lock.users == 0 {
+                locks.remove(&self.key);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use tokio::time::timeout;
+
+    use super::*;
+
+    #[tokio::test(start_paused = true)]
+    async fn lock_serialises_per_key_and_cleans_up() {
+        let keyed_mutex = KeyedMutex::new();
+        let a = keyed_mutex.lock("a").await;
+        let _b = keyed_mutex.lock("b").await;
+        assert!(keyed_mutex.try_lock("a").is_none());
+
+        let cancelled = timeout(Duration::from_millis(1), keyed_mutex.lock("a")).await;
+        assert!(cancelled.is_err());
+        assert_eq!(keyed_mutex.len(), 2);
+
+        drop(a);
+        assert_eq!(keyed_mutex.len(), 1);
+        assert!(keyed_mutex.try_lock("a").is_some());
+    }
+}
@@ -0,0 +1,72 @@
+use tokio::sync::watch;
+
+/// Lets tasks wait until [`Self::count_down`] was called `count` times, e.g.
+/// until every websocket subscription was confirmed.
+///
+/// Once the count reaches 0 it stays there; see [`Barrier`] for a reusable
+/// alternative.
+///
+/// [`Barrier`]: super::Barrier
+pub struct CountDownLatch {
+    count: watch::Sender<usize>,
+}
+
+impl CountDownLatch {
+    #[must_use]
+    pub fn new(count: usize) -> Self {
+        Self {
+            count: watch::Sender::new(count),
+        }
+    }
+
+    /// Decrements the count, releasing the waiting tasks when it reaches 0.
+    /// Does nothing if the count is already 0.
+    pub fn count_down(&self) {
+        self.count.send_if_modified(|count| {
+            let was_positive = *count > 0;
+            *count = count.saturating_sub(1);
+            was_positive
+        });
+    }
+
+    #[must_use]
+    pub fn count(&self) -> usize {
+        *self.count.borrow()
+    }
+
+    /// Waits until the count reaches 0. Cancel safe.
+    pub async fn wait(&self) {
+        self.count
+            .subscribe()
+            .wait_for(|count| *count == 0)
+            .await
+            .expect("sender is owned by the latch");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::Arc, time::Duration};
+
+    use tokio::time::timeout;
+
+    use super::*;
+
+    #[tokio::test(start_paused = true)]
+    async fn wait_completes_after_count_downs() {
+        let latch = Arc::new(CountDownLatch::new(2));
+        let waiter = tokio::spawn({
+            let latch = latch.clone();
+            async move { latch.wait().await }
+        });
+
+        latch.count_down();
+        assert_eq!(latch.count(), 1);
+        assert!(timeout(Duration::from_secs(1), latch.wait()).await.is_err());
+
+        latch.count_down();
+        latch.count_down();
+        assert_eq!(latch.count(), 0);
+        waiter.await.unwrap();
+    }
+}
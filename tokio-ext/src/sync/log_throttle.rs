@@ -3,31 +3,135 @@ use std::{
         OnceLock,
         atomic::{AtomicU64, Ordering},
     },
-    time::Instant,
+    time::{Duration, Instant},
 };
 
-const MIN_INTERVAL_MS: u64 = 1000;
+/// Interval used by [`LogThrottle::default`] and the channel helpers in this
+/// module.
+pub const DEFAULT_INTERVAL: Duration = Duration::from_secs(1);
 
-/// Returns `true` at most once per [`MIN_INTERVAL_MS`] for the given state.
+/// Rate limits a log site to `burst` events per `interval`, counting the
+/// events it suppresses in between.
 ///
-/// `next_log_ms` holds the earliest time (in milliseconds since a process-wide
-/// epoch) at which the next log is allowed; it should start at 0 so that the
-/// first call logs immediately.
-pub(crate) fn should_log(next_log_ms: &AtomicU64) -> bool {
+/// The state consists of atomics only, so a `LogThrottle` can be shared
+/// between threads and placed in a `static` (see [`throttled_warn!`]).
+///
+/// [`throttled_warn!`]: crate::throttled_warn
+pub struct LogThrottle {
+    interval_ms: u64,
+    burst: u64,
+    // Earliest time (in milliseconds since a process-wide epoch) at which the
+    // next window starts. Starts at 0 so that the first call logs immediately.
+    next_window_ms: AtomicU64,
+    logged_in_window: AtomicU64,
+    suppressed: AtomicU64,
+}
+
+impl LogThrottle {
+    /// Creates a throttle allowing `burst` logs per `interval`.
+    #[must_use]
+    pub const fn new(interval: Duration, burst: u64) -> Self {
+        let interval_ms = interval.as_millis();
+        Self {
+            interval_ms: if interval_ms > u64::MAX as u128 {
+                u64::MAX
+            } else {
+                interval_ms as u64
+            },
+            burst,
+            next_window_ms: AtomicU64::new(0),
+            logged_in_window: AtomicU64::new(0),
+            suppressed: AtomicU64::new(0),
+        }
+    }
+
+    /// Returns `Some` with the number of events suppressed since the last
+    /// allowed one if this event should be logged, and `None` (counting the
+    /// event as suppressed) otherwise.
+    pub fn check(&self) -> Option<u64> {
+        let now_ms = now_ms();
+
+        let next = self.next_window_ms.load(Ordering::Relaxed);
+        if now_ms >= next
+            && self
+                .next_window_ms
+                .compare_exchange(
+                    next,
+                    now_ms.saturating_add(self.interval_ms),
+                    Ordering::Relaxed,
+                    Ordering::Relaxed,
+                )
+                .is_ok()
+        {
+            self.logged_in_window.store(0, Ordering::Relaxed);
+        }
+
+        if self.logged_in_window.fetch_add(1, Ordering::Relaxed) < self.burst {
+            Some(self.suppressed.swap(0, Ordering::Relaxed))
+        } else {
+            self.suppressed.fetch_add(1, Ordering::Relaxed);
+            None
+        }
+    }
+
+    /// Returns `true` if this event should be logged. See [`Self::check`].
+    pub fn should_log(&self) -> bool {
+        self.check().is_some()
+    }
+
+    /// Number of events suppressed since the last allowed one.
+    #[must_use]
+    pub fn suppressed(&self) -> u64 {
+        self.suppressed.load(Ordering::Relaxed)
+    }
+}
+
+impl Default for LogThrottle {
+    fn default() -> Self {
+        Self::new(DEFAULT_INTERVAL, 1)
+    }
+}
+
+fn now_ms() -> u64 {
     static EPOCH: OnceLock<Instant> = OnceLock::new();
     let epoch = *EPOCH.get_or_init(Instant::now);
-    let now_ms = u64::try_from(epoch.elapsed().as_millis()).unwrap_or(u64::MAX);
-
-    let next = next_log_ms.load(Ordering::Relaxed);
-    now_ms >= next
-        && next_log_ms
-            .compare_exchange(
-                next,
-                now_ms.saturating_add(MIN_INTERVAL_MS),
-                Ordering::Relaxed,
-                Ordering::Relaxed,
-            )
-            .is_ok()
+    u64::try_from(epoch.elapsed().as_millis()).unwrap_or(u64::MAX)
+}
+
+/// Emits a [`tracing::warn!`] event, throttled per call site with a
+/// [`LogThrottle`]. The number of events suppressed since the last emitted one
+/// is reported in the `suppressed` field.
+///
+/// Defaults to one event per second. The interval and burst can be given
+/// upfront; both must be const expressions.
+///
+/// ```
+/// use std::time::Duration;
+///
+/// use tokio_ext::throttled_warn;
+///
+/// let len = 42;
+/// throttled_warn!(len = len, "Queue is filling up.");
+/// throttled_warn!(
+///     interval: Duration::from_secs(10), burst: 3;
+///     "Order book out of sync; resubscribing."
+/// );
+/// ```
+#[macro_export]
+macro_rules! throttled_warn {
+    (interval: $interval:expr, burst: $burst:expr; $($arg:tt)+) => {{
+        static THROTTLE: $crate::sync::LogThrottle =
+            $crate::sync::LogThrottle::new($interval, $burst);
+        if let ::std::option::Option::Some(suppressed) = THROTTLE.check() {
+            $crate::__private::tracing::warn!(suppressed = suppressed, $($arg)+);
+        }
+    }};
+    ($($arg:tt)+) => {
+        $crate::throttled_warn!(
+            interval: $crate::sync::log_throttle::DEFAULT_INTERVAL, burst: 1;
+            $($arg)+
+        )
+    };
 }
 
 #[cfg(test)]
@@ -36,18 +140,29 @@ mod tests {
 
     #[test]
     fn should_log_true_once_within_interval() {
-        let state = AtomicU64::new(0);
-        assert!(should_log(&state));
-        assert!(!should_log(&state));
-        assert!(!should_log(&state));
+        let throttle = LogThrottle::default();
+        assert!(throttle.should_log());
+        assert!(!throttle.should_log());
+        assert!(!throttle.should_log());
     }
 
     #[test]
     fn should_log_true_again_after_interval() {
-        let state = AtomicU64::new(0);
-        assert!(should_log(&state));
-        // Rewind the next-allowed time to simulate the interval elapsing.
-        state.store(0, Ordering::Relaxed);
-        assert!(should_log(&state));
+        let throttle = LogThrottle::default();
+        assert!(throttle.should_log());
+        // Rewind the next window start to simulate the interval elapsing.
+        throttle.next_window_ms.store(0, Ordering::Relaxed);
+        assert!(throttle.should_log());
+    }
+
+    #[test]
+    fn check_allows_burst_and_reports_suppressed() {
+        let throttle = LogThrottle::new(DEFAULT_INTERVAL, 2);
+        assert_eq!(throttle.check(), Some(0));
+        assert_eq!(throttle.check(), Some(0));
+        assert_eq!(throttle.check(), None);
+        assert_eq!(throttle.check(), None);
+        throttle.next_window_ms.store(0, Ordering::Relaxed);
+        assert_eq!(throttle.check(), Some(2));
     }
 }
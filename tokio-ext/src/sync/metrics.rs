@@ -0,0 +1,182 @@
+use std::{
+    sync::atomic::{AtomicU64, AtomicUsize, Ordering},
+    time::Duration,
+};
+
+use tracing::info;
+
+/// Number of buckets in the occupancy histogram. Each bucket covers an equal
+/// share of the channel capacity, i.e. bucket 0 counts sends that observed the
+/// channel at 0-10% of its capacity.
+pub const OCCUPANCY_BUCKETS: usize = 10;
+
+/// Counters updated by channel senders. All updates are relaxed atomics so
+/// that recording stays cheap on the hot path.
+#[derive(Default)]
+pub(crate) struct ChannelMetrics {
+    peak_len: AtomicUsize,
+    sent: AtomicU64,
+    received: AtomicU64,
+    occupancy: [AtomicU64; OCCUPANCY_BUCKETS],
+    dropped: AtomicU64,
+    rejected: AtomicU64,
+    send_waits: AtomicU64,
+    send_wait_total_ns: AtomicU64,
+    send_wait_max_ns: AtomicU64,
+    closed: AtomicU64,
+}
+
+impl ChannelMetrics {
+    /// Records the channel length observed by a sender.
+    pub(crate) fn record_len(&self, len: usize, capacity: usize) {
+        self.peak_len.fetch_max(len, Ordering::Relaxed);
+        let bucket =
+            (len.saturating_mul(OCCUPANCY_BUCKETS) / capacity.max(1)).min(OCCUPANCY_BUCKETS - 1);
+        self.occupancy[bucket].fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_sent(&self) {
+        self.sent.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_received(&self, count: u64) {
+        self.received.fetch_add(count, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_dropped(&self) {
+        self.dropped.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_rejected(&self) {
+        self.rejected.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_send_wait(&self, wait: Duration) {
+        let wait_ns = u64::try_from(wait.as_nanos()).unwrap_or(u64::MAX);
+        self.send_waits.fetch_add(1, Ordering::Relaxed);
+        self.send_wait_total_ns
+            .fetch_add(wait_ns, Ordering::Relaxed);
+        self.send_wait_max_ns.fetch_max(wait_ns, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_closed(&self) {
+        self.closed.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn rejected(&self) -> u64 {
+        self.rejected.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn snapshot(&self, len: usize, capacity: usize) -> ChannelMetricsSnapshot {
+        ChannelMetricsSnapshot {
+            len,
+            capacity,
+            peak_len: self.peak_len.load(Ordering::Relaxed),
+            sent: self.sent.load(Ordering::Relaxed),
+            received: self.received.load(Ordering::Relaxed),
+            occupancy: std::array::from_fn(|i| self.occupancy[i].load(Ordering::Relaxed)),
+            dropped: self.dropped.load(Ordering::Relaxed),
+            rejected: self.rejected.load(Ordering::Relaxed),
+            send_waits: self.send_waits.load(Ordering::Relaxed),
+            send_wait_total: Duration::from_nanos(self.send_wait_total_ns.load(Ordering::Relaxed)),
+            send_wait_max: Duration::from_nanos(self.send_wait_max_ns.load(Ordering::Relaxed)),
+            closed: self.closed.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// Point-in-time view of a channel's metrics.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ChannelMetricsSnapshot {
+    /// Number of values currently queued.
+    pub len: usize,
+    pub capacity: usize,
+    /// Highest queue length observed by a sender.
+    pub peak_len: usize,
+    /// Number of values accepted by the channel.
+    pub sent: u64,
+    /// Number of values taken out of the channel. Only counted by channels
+    /// with their own receiver, i.e. not by [`mpsc`](super::mpsc) and
+    /// [`broadcast`](super::broadcast), which use Tokio's.
+    pub received: u64,
+    /// Histogram of the queue length observed by senders. See
+    /// [`OCCUPANCY_BUCKETS`].
+    pub occupancy: [u64; OCCUPANCY_BUCKETS],
+    /// Number of values discarded by the channel, e.g. because it was full or
+    /// a newer value replaced them before they were received.
+    pub dropped: u64,
+    /// Number of values given back to the sender because the channel was
+    /// full.
+    pub rejected: u64,
+    /// Number of sends that may have waited for capacity.
+    pub send_waits: u64,
+    pub send_wait_total: Duration,
+    pub send_wait_max: Duration,
+    /// Number of sends that failed because the channel was closed.
+    pub closed: u64,
+}
+
+impl ChannelMetricsSnapshot {
+    #[must_use]
+    pub fn send_wait_mean(&self) -> Duration {
+        if self.send_waits == 0 {
+            return Duration::ZERO;
+        }
+        let mean_ns = self.send_wait_total.as_nanos() / u128::from(self.send_waits);
+        Duration::from_nanos(u64::try_from(mean_ns).unwrap_or(u64::MAX))
+    }
+
+    /// Emits the snapshot as fields of a [`tracing::info!`] event.
+    pub fn log(&self, channel: &str) {
+        self.log_with_lane(channel, None);
+    }
+
+    /// Like [`Self::log`], for a lane of a [`priority`](super::priority)
+    /// channel.
+    pub(crate) fn log_lane(&self, channel: &str, lane: usize) {
+        self.log_with_lane(channel, Some(lane));
+    }
+
+    fn log_with_lane(&self, channel: &str, lane: Option<usize>) {
+        info!(
+            channel = channel,
+            lane = lane,
+            len = self.len,
+            capacity = self.capacity,
+            peak_len = self.peak_len,
+            sent = self.sent,
+            received = self.received,
+            occupancy = ?self.occupancy,
+            dropped = self.dropped,
+            rejected = self.rejected,
+            send_waits = self.send_waits,
+            send_wait_mean = ?self.send_wait_mean(),
+            send_wait_max = ?self.send_wait_max,
+            closed = self.closed,
+            "Channel metrics."
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn record_len_fills_occupancy_buckets() {
+        let metrics = ChannelMetrics::default();
+        metrics.record_len(0, 10);
+        metrics.record_len(5, 10);
+        metrics.record_len(10, 10);
+
+        let snapshot = metrics.snapshot(3, 10);
+        assert_eq!(snapshot.peak_len, 10);
+        assert_eq!(snapshot.occupancy[0], 1);
+        assert_eq!(snapshot.occupancy[5], 1);
+        assert_eq!(snapshot.occupancy[OCCUPANCY_BUCKETS - 1], 1);
+    }
+}
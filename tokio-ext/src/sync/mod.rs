@@ -1,3 +1,26 @@
+pub mod barrier;
 pub mod broadcast;
-mod log_throttle;
+pub mod conflate;
+pub mod keyed_mutex;
+pub mod latch;
+pub mod log_throttle;
+pub mod metrics;
 pub mod mpsc;
+pub mod oneshot;
+pub mod overflow;
+pub mod priority;
+pub mod rate_limiter;
+pub mod ready_gate;
+mod shared;
+pub mod single_flight;
+pub mod watch;
+
+pub use self::{
+    barrier::{Barrier, BarrierWaitResult},
+    keyed_mutex::{KeyedMutex, KeyedMutexGuard},
+    latch::CountDownLatch,
+    log_throttle::LogThrottle,
+    rate_limiter::RateLimiter,
+    ready_gate::{NotReady, ReadyGate},
+    single_flight::SingleFlight,
+};
@@ -1,20 +1,258 @@
-use std::{panic::Location, sync::atomic::AtomicU64};
+use std::{
+    collections::HashMap,
+    ops::Deref,
+    panic::Location,
+    sync::{Arc, RwLock},
+    time::Instant,
+};
 
 use tokio::sync::mpsc::{
-    PermitIterator, Sender,
+    PermitIterator,
     error::{SendError, TrySendError},
 };
 use tracing::warn;
 
-use super::log_throttle::should_log;
+use super::{
+    log_throttle::LogThrottle,
+    metrics::{ChannelMetrics, ChannelMetricsSnapshot},
+};
+
+/// Creates a bounded mpsc channel whose [`Sender`] logs on high channel usage
+/// once more than half of the capacity is in use.
+///
+/// # Panics
+///
+/// Panics if the capacity is 0.
+#[must_use]
+pub fn channel<T>(name: impl Into<String>, capacity: usize) -> (Sender<T>, Receiver<T>) {
+    channel_with_high_water_mark(name, capacity, capacity / 2)
+}
+
+/// Creates a bounded mpsc channel whose [`Sender`] logs on high channel usage
+/// once more than `high_water_mark` values are queued.
+///
+/// # Panics
+///
+/// Panics if the capacity is 0.
+#[must_use]
+pub fn channel_with_high_water_mark<T>(
+    name: impl Into<String>,
+    capacity: usize,
+    high_water_mark: usize,
+) -> (Sender<T>, Receiver<T>) {
+    let (tx, rx) = tokio::sync::mpsc::channel(capacity);
+    (
+        Sender {
+            tx,
+            state: Arc::new(State {
+                name: name.into(),
+                high_water_mark,
+                call_sites: RwLock::default(),
+                metrics: ChannelMetrics::default(),
+            }),
+        },
+        rx,
+    )
+}
+
+pub use tokio::sync::mpsc::Receiver;
+
+/// A [`tokio::sync::mpsc::Sender`] that keeps its backpressure logging state
+/// per channel and per call site, so that a saturated channel does not
+/// suppress the warnings of other channels.
+pub struct Sender<T> {
+    tx: tokio::sync::mpsc::Sender<T>,
+    state: Arc<State>,
+}
+
+struct State {
+    name: String,
+    high_water_mark: usize,
+    call_sites: RwLock<HashMap<&'static Location<'static>, CallSite>>,
+    metrics: ChannelMetrics,
+}
+
+#[derive(Default)]
+struct CallSite {
+    usage_log_throttle: LogThrottle,
+    discard_log_throttle: LogThrottle,
+}
+
+impl<T> Clone for Sender<T> {
+    fn clone(&self) -> Self {
+        Self {
+            tx: self.tx.clone(),
+            state: self.state.clone(),
+        }
+    }
+}
+
+impl<T> Deref for Sender<T> {
+    type Target = tokio::sync::mpsc::Sender<T>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.tx
+    }
+}
+
+impl<T> Sender<T> {
+    #[must_use]
+    pub fn name(&self) -> &str {
+        &self.state.name
+    }
+
+    #[must_use]
+    pub fn high_water_mark(&self) -> usize {
+        self.state.high_water_mark
+    }
+
+    /// Returns the channel metrics recorded by all clones of this sender.
+    #[must_use]
+    pub fn metrics(&self) -> ChannelMetricsSnapshot {
+        let capacity = self.tx.max_capacity();
+        self.state
+            .metrics
+            .snapshot(capacity - self.tx.capacity(), capacity)
+    }
+
+    /// Emits the channel metrics as fields of a [`tracing::info!`] event.
+    pub fn log_metrics(&self) {
+        self.metrics().log(&self.state.name);
+    }
+
+    /// Sends a value, logging on high channel usage. To keep the logging off
+    /// the hot path, the warning is emitted at most once per second per
+    /// channel and call site.
+    #[track_caller]
+    pub fn send_log_backpressure(
+        &self,
+        value: T,
+    ) -> impl Future<Output = Result<(), SendError<T>>> {
+        self.log_high_usage(Location::caller(), "send");
+        async move {
+            let start = Instant::now();
+            let result = self.tx.send(value).await;
+            self.record_send(start, result.is_err());
+            if result.is_ok() {
+                self.state.metrics.record_sent();
+            }
+            result
+        }
+    }
+
+    /// Attempts to send a value, discarding it if the channel is full. Logs on
+    /// high channel usage and on discard, at most once per second per channel
+    /// and call site.
+    #[track_caller]
+    pub fn try_send_discard_full_log_backpressure(&self, value: T) -> Result<(), SendError<T>> {
+        let caller = Location::caller();
+        self.log_high_usage(caller, "try send");
+        match self.tx.try_send(value) {
+            Ok(()) => {
+                self.state.metrics.record_sent();
+                Ok(())
+            }
+            Err(TrySendError::Full(_)) => {
+                let metrics = &self.state.metrics;
+                metrics.record_dropped();
+                if let Some(suppressed) =
+                    self.check_log(caller, |call_site| &call_site.discard_log_throttle)
+                {
+                    warn!(
+                        channel = self.state.name,
+                        dropped = metrics.dropped(),
+                        suppressed = suppressed,
+                        file = caller.file(),
+                        line = caller.line(),
+                        "Discarded value in mpsc try send."
+                    );
+                }
+                Ok(())
+            }
+            Err(TrySendError::Closed(value)) => {
+                self.state.metrics.record_closed();
+                Err(SendError(value))
+            }
+        }
+    }
+
+    /// Reserves capacity to send `size` values, logging on high channel usage
+    /// at most once per second per channel and call site.
+    #[track_caller]
+    pub fn reserve_many_log_backpressure(
+        &self,
+        size: usize,
+    ) -> impl Future<Output = Result<PermitIterator<'_, T>, SendError<()>>> {
+        self.log_high_usage(Location::caller(), "reserve many");
+        async move {
+            let start = Instant::now();
+            let result = self.tx.reserve_many(size).await;
+            self.record_send(start, result.is_err());
+            result
+        }
+    }
+
+    fn record_send(&self, start: Instant, closed: bool) {
+        let metrics = &self.state.metrics;
+        metrics.record_send_wait(start.elapsed());
+        if closed {
+            metrics.record_closed();
+        }
+    }
+
+    fn log_high_usage(&self, caller: &'static Location<'static>, operation: &str) {
+        let capacity = self.tx.max_capacity();
+        let len = capacity - self.tx.capacity();
+        self.state.metrics.record_len(len, capacity);
+        if len > self.state.high_water_mark
+            && let Some(suppressed) =
+                self.check_log(caller, |call_site| &call_site.usage_log_throttle)
+        {
+            warn!(
+                channel = self.state.name,
+                len = len,
+                capacity = capacity,
+                high_water_mark = self.state.high_water_mark,
+                suppressed = suppressed,
+                file = caller.file(),
+                line = caller.line(),
+                "High channel usage in mpsc {operation}."
+            );
+        }
+    }
+
+    fn check_log(
+        &self,
+        caller: &'static Location<'static>,
+        log_throttle: impl Fn(&CallSite) -> &LogThrottle,
+    ) -> Option<u64> {
+        let call_sites = &self.state.call_sites;
+        if let Some(call_site) = call_sites
+            .read()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+            .get(caller)
+        {
+            return log_throttle(call_site).check();
+        }
+        let mut call_sites = call_sites
+            .write()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        log_throttle(call_sites.entry(caller).or_default()).check()
+    }
+}
 
 /// Extension methods that log on high channel usage. To keep the logging off
 /// the hot path, each warning is emitted at most once per second
-/// (process-wide per warning site, since the underlying [`Sender`] holds no
-/// per-channel state for it).
+/// (process-wide per warning site, since the underlying
+/// [`tokio::sync::mpsc::Sender`] holds no per-channel state for it). Prefer
+/// [`channel`], whose [`Sender`] keeps the state per channel and call site.
 pub trait SenderExt<'a, T: 'a> {
     fn send_log_backpressure(&self, value: T) -> impl Future<Output = Result<(), SendError<T>>>;
 
+    /// Discarded values are not counted, as there is nowhere to record them.
+    #[deprecated(
+        note = "use `mpsc::channel`, whose `Sender::try_send_discard_full_log_backpressure` counts discarded values in its metrics"
+    )]
     fn try_send_discard_full_log_backpressure(&self, value: T) -> Result<(), SendError<T>>;
 
     fn reserve_many_log_backpressure(
@@ -23,7 +261,7 @@ pub trait SenderExt<'a, T: 'a> {
     ) -> impl Future<Output = Result<PermitIterator<'a, T>, SendError<()>>>;
 }
 
-impl<'a, T: 'a> SenderExt<'a, T> for Sender<T> {
+impl<'a, T: 'a> SenderExt<'a, T> for tokio::sync::mpsc::Sender<T> {
     #[track_caller]
     fn send_log_backpressure(&self, value: T) -> impl Future<Output = Result<(), SendError<T>>> {
         let caller = Location::caller();
@@ -32,13 +270,10 @@ impl<'a, T: 'a> SenderExt<'a, T> for Sender<T> {
 
     #[track_caller]
     fn try_send_discard_full_log_backpressure(&self, value: T) -> Result<(), SendError<T>> {
-        static NEXT_USAGE_LOG_MS: AtomicU64 = AtomicU64::new(0);
-        static NEXT_DISCARD_LOG_MS: AtomicU64 = AtomicU64::new(0);
-
         let capacity = self.max_capacity();
         let len = capacity - self.capacity();
-        if len > capacity / 2 && should_log(&NEXT_USAGE_LOG_MS) {
-            warn!(
+        if len > capacity / 2 {
+            crate::throttled_warn!(
                 len = len,
                 capacity = capacity,
                 "High channel usage in mpsc try send."
@@ -47,9 +282,7 @@ impl<'a, T: 'a> SenderExt<'a, T> for Sender<T> {
         match self.try_send(value) {
             Ok(()) => Ok(()),
             Err(TrySendError::Full(_)) => {
-                if should_log(&NEXT_DISCARD_LOG_MS) {
-                    warn!("Discarded value in mpsc try send.");
-                }
+                crate::throttled_warn!("Discarded value in mpsc try send.");
                 Ok(())
             }
             Err(TrySendError::Closed(value)) => Err(SendError(value)),
@@ -67,16 +300,14 @@ impl<'a, T: 'a> SenderExt<'a, T> for Sender<T> {
 }
 
 async fn send_log_backpressure_impl<T>(
-    sender: &Sender<T>,
+    sender: &tokio::sync::mpsc::Sender<T>,
     value: T,
     caller: &'static Location<'static>,
 ) -> Result<(), SendError<T>> {
-    static NEXT_LOG_MS: AtomicU64 = AtomicU64::new(0);
-
     let capacity = sender.max_capacity();
     let len = capacity - sender.capacity();
-    if len > capacity / 2 && should_log(&NEXT_LOG_MS) {
-        warn!(
+    if len > capacity / 2 {
+        crate::throttled_warn!(
             len = len,
             capacity = capacity,
             file = caller.file(),
@@ -88,16 +319,14 @@ async fn send_log_backpressure_impl<T>(
 }
 
 async fn reserve_many_log_backpressure_impl<'a, T: 'a>(
-    sender: &'a Sender<T>,
+    sender: &'a tokio::sync::mpsc::Sender<T>,
     size: usize,
     caller: &'static Location<'static>,
 ) -> Result<PermitIterator<'a, T>, SendError<()>> {
-    static NEXT_LOG_MS: AtomicU64 = AtomicU64::new(0);
-
     let capacity = sender.max_capacity();
     let len = capacity - sender.capacity();
-    if len > capacity / 2 && should_log(&NEXT_LOG_MS) {
-        warn!(
+    if len > capacity / 2 {
+        crate::throttled_warn!(
             len = len,
             capacity = capacity,
             file = caller.file(),
@@ -107,3 +336,21 @@ async fn reserve_many_log_backpressure_impl<'a, T: 'a>(
     }
     sender.reserve_many(size).await
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn try_send_discard_full_discards_when_full() {
+        let (tx, mut rx) = channel("test", 1);
+        tx.try_send_discard_full_log_backpressure(1).unwrap();
+        tx.try_send_discard_full_log_backpressure(2).unwrap();
+
+        assert_eq!(tx.metrics().dropped, 1);
+        drop(tx);
+
+        assert_eq!(rx.recv().await, Some(1));
+        assert_eq!(rx.recv().await, None);
+    }
+}
@@ -0,0 +1,49 @@
+use std::time::Duration;
+
+use tokio::sync::oneshot::Receiver;
+
+#[derive(thiserror::Error, Debug, PartialEq, Eq)]
+pub enum RecvTimeoutError {
+    #[error("Oneshot sender was dropped.")]
+    Closed,
+    #[error("Timed out receiving from oneshot.")]
+    Timeout,
+}
+
+pub trait ReceiverExt<T> {
+    /// Receives the value, giving up after `timeout`. The receiver can be
+    /// awaited again after a timeout.
+    fn recv_timeout(
+        &mut self,
+        timeout: Duration,
+    ) -> impl Future<Output = Result<T, RecvTimeoutError>>;
+}
+
+impl<T> ReceiverExt<T> for Receiver<T> {
+    async fn recv_timeout(&mut self, timeout: Duration) -> Result<T, RecvTimeoutError> {
+        match tokio::time::timeout(timeout, self).await {
+            Ok(Ok(value)) => Ok(value),
+            Ok(Err(_)) => Err(RecvTimeoutError::Closed),
+            Err(_) => Err(RecvTimeoutError::Timeout),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn recv_timeout_distinguishes_timeout_from_closed() {
+        let (tx, mut rx) = tokio::sync::oneshot::channel::<()>();
+        assert_eq!(
+            rx.recv_timeout(Duration::from_millis(1)).await,
+            Err(RecvTimeoutError::Timeout)
+        );
+        drop(tx);
+        assert_eq!(
+            rx.recv_timeout(Duration::from_millis(1)).await,
+            Err(RecvTimeoutError::Closed)
+        );
+    }
+}
@@ -0,0 +1,396 @@
+use std::{collections::VecDeque, fmt, panic::Location, pin::pin, time::Duration};
+
+use tokio::{
+    sync::mpsc::error::{SendTimeoutError, TryRecvError},
+    time::Instant,
+};
+use tracing::warn;
+
+use super::{
+    log_throttle::LogThrottle,
+    metrics::{ChannelMetrics, ChannelMetricsSnapshot},
+    shared,
+};
+
+/// What a [`Sender`] does with a value when the channel is full.
+pub enum OverflowPolicy<T> {
+    /// Discard the value being sent.
+    DropNewest,
+    /// Discard the oldest queued value to make room for the one being sent.
+    DropOldest,
+    /// Wait for capacity up to the given duration, then give the value back.
+    Timeout(Duration),
+    /// Replace the most recent queued value for which the predicate returns
+    /// `true`, falling back to [`OverflowPolicy::DropOldest`] if there is
+    /// none. The predicate is called with the queued and the sent value.
+    Coalesce(CoalescePredicate<T>),
+}
+
+pub type CoalescePredicate<T> = Box<dyn Fn(&T, &T) -> bool + Send + Sync>;
+
+impl<T> OverflowPolicy<T> {
+    /// Coalesces values that share the same key. See
+    /// [`OverflowPolicy::Coalesce`].
+    pub fn coalesce_by_key<K, F>(key: F) -> Self
+    where
+        K: PartialEq,
+        F: Fn(&T) -> K + Send + Sync + 'static,
+    {
+        Self::Coalesce(Box::new(move |queued, sent| key(queued) == key(sent)))
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            Self::DropNewest => "drop_newest",
+            Self::DropOldest => "drop_oldest",
+            Self::Timeout(_) => "timeout",
+            Self::Coalesce(_) => "coalesce",
+        }
+    }
+}
+
+impl<T> fmt::Debug for OverflowPolicy<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Timeout(timeout) => f.debug_tuple("Timeout").field(timeout).finish(),
+            _ => f.write_str(self.name()),
+        }
+    }
+}
+
+/// Creates a bounded multi-producer, single-consumer channel which applies
+/// `policy` when a value is sent to a full channel.
+///
+/// Senders log on high channel usage (more than half of the capacity in use)
+/// and on dropped values, at most once per second per channel each. Dropped
+/// values, and values given back to the sender because the channel is full,
+/// are counted in the channel [metrics](Sender::metrics).
+///
+/// # Panics
+///
+/// Panics if the capacity is 0.
+#[must_use]
+pub fn channel<T>(
+    name: impl Into<String>,
+    capacity: usize,
+    policy: OverflowPolicy<T>,
+) -> (Sender<T>, Receiver<T>) {
+    assert!(capacity > 0, "overflow channel requires capacity > 0");
+    let (tx, rx) = shared::channel(
+        VecDeque::with_capacity(capacity),
+        1,
+        State {
+            name: name.into(),
+            capacity,
+            policy,
+            usage_log_throttle: LogThrottle::default(),
+            drop_log_throttle: LogThrottle::default(),
+            reject_log_throttle: LogThrottle::default(),
+            metrics: ChannelMetrics::default(),
+        },
+    );
+    (Sender { shared: tx }, Receiver { shared: rx })
+}
+
+struct State<T> {
+    name: String,
+    capacity: usize,
+    policy: OverflowPolicy<T>,
+    usage_log_throttle: LogThrottle,
+    drop_log_throttle: LogThrottle,
+    reject_log_throttle: LogThrottle,
+    metrics: ChannelMetrics,
+}
+
+enum Push<T> {
+    Queued,
+    Dropped,
+    Full(T),
+    Closed(T),
+}
+
+pub struct Sender<T> {
+    shared: shared::Sender<VecDeque<T>, State<T>>,
+}
+
+impl<T> Clone for Sender<T> {
+    fn clone(&self) -> Self {
+        Self {
+            shared: self.shared.clone(),
+        }
+    }
+}
+
+impl<T> Sender<T> {
+    #[must_use]
+    pub fn name(&self) -> &str {
+        &self.shared.state().name
+    }
+
+    #[must_use]
+    pub fn policy(&self) -> &OverflowPolicy<T> {
+        &self.shared.state().policy
+    }
+
+    #[must_use]
+    pub fn is_closed(&self) -> bool {
+        self.shared.is_closed()
+    }
+
+    #[must_use]
+    pub fn metrics(&self) -> ChannelMetricsSnapshot {
+        let state = self.shared.state();
+        let len = self.shared.queue().len();
+        state.metrics.snapshot(len, state.capacity)
+    }
+
+    /// Emits the channel metrics as fields of a [`tracing::info!`] event.
+    pub fn log_metrics(&self) {
+        self.metrics().log(&self.shared.state().name);
+    }
+
+    /// Sends a value, applying the overflow policy if the channel is full.
+    ///
+    /// Only [`OverflowPolicy::Timeout`] waits for capacity; it returns
+    /// [`SendTimeoutError::Timeout`] with the value if none frees up in time.
+    /// Other policies complete immediately, successfully even if a value was
+    /// dropped.
+    #[track_caller]
+    pub fn send(&self, value: T) -> impl Future<Output = Result<(), SendTimeoutError<T>>> {
+        let caller = Location::caller();
+        async move {
+            let state = self.shared.state();
+            let OverflowPolicy::Timeout(timeout) = state.policy else {
+                return match self.push(value, caller) {
+                    Push::Queued | Push::Dropped => Ok(()),
+                    Push::Full(value) | Push::Closed(value) => Err(SendTimeoutError::Closed(value)),
+                };
+            };
+
+            let start = Instant::now();
+            let deadline = start + timeout;
+            let mut value = value;
+            let result = loop {
+                // Register for wakeups before checking capacity so that a
+                // value received in between is not missed.
+                let mut value_received = pin!(self.shared.value_received(0).notified());
+                value_received.as_mut().enable();
+                match self.push(value, caller) {
+                    Push::Queued | Push::Dropped => break Ok(()),
+                    Push::Closed(value) => break Err(SendTimeoutError::Closed(value)),
+                    Push::Full(full) => value = full,
+                }
+                if tokio::time::timeout_at(deadline, value_received)
+                    .await
+                    .is_err()
+                {
+                    self.record_rejected(caller);
+                    break Err(SendTimeoutError::Timeout(value));
+                }
+            };
+            state.metrics.record_send_wait(start.elapsed());
+            result
+        }
+    }
+
+    /// Sends a value without waiting, applying the overflow policy if the
+    /// channel is full. With [`OverflowPolicy::Timeout`], a full channel
+    /// returns [`SendTimeoutError::Timeout`] with the value immediately.
+    #[track_caller]
+    pub fn try_send(&self, value: T) -> Result<(), SendTimeoutError<T>> {
+        let caller = Location::caller();
+        match self.push(value, caller) {
+            Push::Queued | Push::Dropped => Ok(()),
+            Push::Full(value) => {
+                self.record_rejected(caller);
+                Err(SendTimeoutError::Timeout(value))
+            }
+            Push::Closed(value) => Err(SendTimeoutError::Closed(value)),
+        }
+    }
+
+    fn push(&self, value: T, caller: &'static Location<'static>) -> Push<T> {
+        let state = self.shared.state();
+        let Some(mut queue) = self.shared.open_queue() else {
+            state.metrics.record_closed();
+            return Push::Closed(value);
+        };
+
+        let len = queue.len();
+        state.metrics.record_len(len, state.capacity);
+        if len > state.capacity / 2
+            && let Some(suppressed) = state.usage_log_throttle.check()
+        {
+            warn!(
+                channel = state.name,
+                len = len,
+                capacity = state.capacity,
+                suppressed = suppressed,
+                file = caller.file(),
+                line = caller.line(),
+                "High channel usage in overflow send."
+            );
+        }
+
+        let push = if len < state.capacity {
+            queue.push_back(value);
+            state.metrics.record_sent();
+            Push::Queued
+        } else {
+            match &state.policy {
+                OverflowPolicy::DropNewest => Push::Dropped,
+                OverflowPolicy::DropOldest => {
+                    queue.pop_front();
+                    queue.push_back(value);
+                    state.metrics.record_sent();
+                    Push::Dropped
+                }
+                OverflowPolicy::Timeout(_) => Push::Full(value),
+                OverflowPolicy::Coalesce(same) => {
+                    if let Some(queued) = queue.iter_mut().rev().find(|queued| same(queued, &value))
+                    {
+                        *queued = value;
+                    } else {
+                        queue.pop_front();
+                        queue.push_back(value);
+                    }
+                    state.metrics.record_sent();
+                    Push::Dropped
+                }
+            }
+        };
+        drop(queue);
+
+        match push {
+            Push::Queued => self.shared.notify_sent(),
+            Push::Dropped => self.record_dropped(caller),
+            Push::Full(_) | Push::Closed(_) => {}
+        }
+        push
+    }
+
+    fn record_dropped(&self, caller: &'static Location<'static>) {
+        let state = self.shared.state();
+        state.metrics.record_dropped();
+        if let Some(suppressed) = state.drop_log_throttle.check() {
+            warn!(
+                channel = state.name,
+                policy = state.policy.name(),
+                dropped = state.metrics.dropped(),
+                suppressed = suppressed,
+                file = caller.file(),
+                line = caller.line(),
+                "Dropped value in overflow send."
+            );
+        }
+    }
+
+    fn record_rejected(&self, caller: &'static Location<'static>) {
+        let state = self.shared.state();
+        state.metrics.record_rejected();
+        if let Some(suppressed) = state.reject_log_throttle.check() {
+            warn!(
+                channel = state.name,
+                rejected = state.metrics.rejected(),
+                suppressed = suppressed,
+                file = caller.file(),
+                line = caller.line(),
+                "Channel full in overflow send."
+            );
+        }
+    }
+}
+
+pub struct Receiver<T> {
+    shared: shared::Receiver<VecDeque<T>, State<T>>,
+}
+
+impl<T> Receiver<T> {
+    /// Receives the next value, or `None` once the channel is empty and all
+    /// senders have been dropped.
+    pub async fn recv(&mut self) -> Option<T> {
+        let (_, value) = self.shared.recv(pop).await?;
+        Some(value)
+    }
+
+    pub fn try_recv(&mut self) -> Result<T, TryRecvError> {
+        let (_, value) = self.shared.try_recv(pop)?;
+        Ok(value)
+    }
+
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.shared.queue().len()
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+fn pop<T>(state: &State<T>, queue: &mut VecDeque<T>) -> Option<(usize, T)> {
+    let value = queue.pop_front()?;
+    state.metrics.record_received(1);
+    Some((0, value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn drop_oldest_keeps_newest() {
+        let (tx, mut rx) = channel("test", 2, OverflowPolicy::DropOldest);
+        for i in 0..4 {
+            tx.send(i).await.unwrap();
+        }
+        assert_eq!(tx.metrics().dropped, 2);
+        drop(tx);
+
+        assert_eq!(rx.recv().await, Some(2));
+        assert_eq!(rx.recv().await, Some(3));
+        assert_eq!(rx.recv().await, None);
+    }
+
+    #[tokio::test]
+    async fn coalesce_by_key_replaces_queued_value() {
+        let (tx, mut rx) = channel(
+            "test",
+            2,
+            OverflowPolicy::coalesce_by_key(|(key, _): &(&str, u32)| *key),
+        );
+        tx.send(("a", 1)).await.unwrap();
+        tx.send(("b", 1)).await.unwrap();
+        tx.send(("a", 2)).await.unwrap();
+        drop(tx);
+
+        assert_eq!(rx.recv().await, Some(("a", 2)));
+        assert_eq!(rx.recv().await, Some(("b", 1)));
+        assert_eq!(rx.recv().await, None);
+    }
+
+    #[tokio::test]
+    async fn timeout_gives_value_back() {
+        let (tx, _rx) = channel("test", 1, OverflowPolicy::Timeout(Duration::from_millis(1)));
+        tx.send(1).await.unwrap();
+        assert!(matches!(
+            tx.send(2).await,
+            Err(SendTimeoutError::Timeout(2))
+        ));
+        assert_eq!(tx.metrics().dropped, 0);
+        assert_eq!(tx.metrics().rejected, 1);
+    }
+
+    #[tokio::test]
+    async fn recv_does_not_lose_value_sent_before_close() {
+        for i in 0..1000 {
+            let (tx, mut rx) = channel("test", 1, OverflowPolicy::DropNewest);
+            // Sends and drops the last sender concurrently with the receiver.
+            let sender = std::thread::spawn(move || tx.try_send(i).unwrap());
+            assert_eq!(rx.recv().await, Some(i));
+            assert_eq!(rx.recv().await, None);
+            sender.join().unwrap();
+        }
+    }
+}
@@ -0,0 +1,327 @@
+use std::{collections::VecDeque, panic::Location, pin::pin};
+
+use tokio::{
+    sync::mpsc::error::{SendError, TryRecvError, TrySendError},
+    time::Instant,
+};
+use tracing::warn;
+
+use super::{
+    log_throttle::LogThrottle,
+    metrics::{ChannelMetrics, ChannelMetricsSnapshot},
+    shared,
+};
+
+/// How a [`Receiver`] picks the lane to receive from.
+#[derive(Clone, Debug)]
+pub enum Dequeue {
+    /// Always receive from the highest priority (lowest index) non-empty
+    /// lane. Lower priority lanes may starve.
+    Strict,
+    /// Receive from each lane in proportion to its weight, in priority order
+    /// within a round. Empty lanes give up their share of the round.
+    Weighted(Vec<u32>),
+}
+
+/// Creates a multi-producer, single-consumer channel with one bounded lane per
+/// capacity, lane 0 being the highest priority. Useful to keep control
+/// messages from queueing up behind data messages.
+///
+/// Senders log on high usage of a lane (more than half of its capacity in
+/// use), at most once per second per lane. Each lane has its own
+/// [metrics](Sender::metrics).
+///
+/// # Panics
+///
+/// Panics if there are no lanes, any capacity is 0, or the weights of
+/// [`Dequeue::Weighted`] do not match the lanes or contain a 0.
+#[must_use]
+pub fn channel<T>(
+    name: impl Into<String>,
+    capacities: impl IntoIterator<Item = usize>,
+    dequeue: Dequeue,
+) -> (Sender<T>, Receiver<T>) {
+    let capacities = capacities.into_iter().collect::<Vec<_>>();
+    assert!(!capacities.is_empty(), "priority channel requires a lane");
+    assert!(
+        capacities.iter().all(|capacity| *capacity > 0),
+        "priority channel requires capacity > 0 for every lane"
+    );
+    let credits = match &dequeue {
+        Dequeue::Strict => Vec::new(),
+        Dequeue::Weighted(weights) => {
+            assert_eq!(
+                weights.len(),
+                capacities.len(),
+                "priority channel requires a weight for every lane"
+            );
+            assert!(
+                weights.iter().all(|weight| *weight > 0),
+                "priority channel requires weight > 0 for every lane"
+            );
+            weights.clone()
+        }
+    };
+
+    let lanes = Lanes {
+        queues: capacities
+            .iter()
+            .map(|capacity| VecDeque::with_capacity(*capacity))
+            .collect(),
+        credits,
+    };
+    let (tx, rx) = shared::channel(
+        lanes,
+        capacities.len(),
+        State {
+            name: name.into(),
+            usage_log_throttles: capacities.iter().map(|_| LogThrottle::default()).collect(),
+            metrics: capacities
+                .iter()
+                .map(|_| ChannelMetrics::default())
+                .collect(),
+            capacities,
+            dequeue,
+        },
+    );
+    (Sender { shared: tx }, Receiver { shared: rx })
+}
+
+struct State {
+    name: String,
+    capacities: Vec<usize>,
+    dequeue: Dequeue,
+    // Per lane.
+    usage_log_throttles: Vec<LogThrottle>,
+    metrics: Vec<ChannelMetrics>,
+}
+
+struct Lanes<T> {
+    queues: Vec<VecDeque<T>>,
+    // Receives left per lane in the current weighted round.
+    credits: Vec<u32>,
+}
+
+pub struct Sender<T> {
+    shared: shared::Sender<Lanes<T>, State>,
+}
+
+impl<T> Clone for Sender<T> {
+    fn clone(&self) -> Self {
+        Self {
+            shared: self.shared.clone(),
+        }
+    }
+}
+
+impl<T> Sender<T> {
+    #[must_use]
+    pub fn name(&self) -> &str {
+        &self.shared.state().name
+    }
+
+    #[must_use]
+    pub fn lanes(&self) -> usize {
+        self.shared.state().capacities.len()
+    }
+
+    /// Returns the metrics of a lane.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the lane does not exist.
+    #[must_use]
+    pub fn metrics(&self, lane: usize) -> ChannelMetricsSnapshot {
+        let state = self.shared.state();
+        let len = self.shared.queue().queues[lane].len();
+        state.metrics[lane].snapshot(len, state.capacities[lane])
+    }
+
+    /// Emits the metrics of every lane as fields of a [`tracing::info!`]
+    /// event per lane.
+    pub fn log_metrics(&self) {
+        for lane in 0..self.lanes() {
+            self.metrics(lane).log_lane(&self.shared.state().name, lane);
+        }
+    }
+
+    /// Sends a value to a lane, waiting for capacity in that lane.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the lane does not exist.
+    #[track_caller]
+    pub fn send(&self, lane: usize, value: T) -> impl Future<Output = Result<(), SendError<T>>> {
+        let caller = Location::caller();
+        assert!(lane < self.lanes(), "priority channel lane out of bounds");
+        async move {
+            let start = Instant::now();
+            let mut value = value;
+            let result = loop {
+                // Register for wakeups before checking capacity so that a
+                // value received in between is not missed.
+                let mut value_received = pin!(self.shared.value_received(lane).notified());
+                value_received.as_mut().enable();
+                match self.push(lane, value, caller) {
+                    Ok(()) => break Ok(()),
+                    Err(TrySendError::Closed(value)) => break Err(SendError(value)),
+                    Err(TrySendError::Full(full)) => value = full,
+                }
+                value_received.await;
+            };
+            self.shared.state().metrics[lane].record_send_wait(start.elapsed());
+            result
+        }
+    }
+
+    /// Attempts to send a value to a lane without waiting.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the lane does not exist.
+    #[track_caller]
+    pub fn try_send(&self, lane: usize, value: T) -> Result<(), TrySendError<T>> {
+        assert!(lane < self.lanes(), "priority channel lane out of bounds");
+        let result = self.push(lane, value, Location::caller());
+        if let Err(TrySendError::Full(_)) = result {
+            self.shared.state().metrics[lane].record_rejected();
+        }
+        result
+    }
+
+    fn push(
+        &self,
+        lane: usize,
+        value: T,
+        caller: &'static Location<'static>,
+    ) -> Result<(), TrySendError<T>> {
+        let state = self.shared.state();
+        let metrics = &state.metrics[lane];
+        let Some(mut lanes) = self.shared.open_queue() else {
+            metrics.record_closed();
+            return Err(TrySendError::Closed(value));
+        };
+
+        let queue = &mut lanes.queues[lane];
+        let len = queue.len();
+        let capacity = state.capacities[lane];
+        metrics.record_len(len, capacity);
+        if len > capacity / 2
+            && let Some(suppressed) = state.usage_log_throttles[lane].check()
+        {
+            warn!(
+                channel = state.name,
+                lane = lane,
+                len = len,
+                capacity = capacity,
+                suppressed = suppressed,
+                file = caller.file(),
+                line = caller.line(),
+                "High channel usage in priority send."
+            );
+        }
+        if len >= capacity {
+            return Err(TrySendError::Full(value));
+        }
+        queue.push_back(value);
+        drop(lanes);
+
+        metrics.record_sent();
+        self.shared.notify_sent();
+        Ok(())
+    }
+}
+
+pub struct Receiver<T> {
+    shared: shared::Receiver<Lanes<T>, State>,
+}
+
+impl<T> Receiver<T> {
+    /// Receives the next value along with its lane, or `None` once every lane
+    /// is empty and all senders have been dropped.
+    pub async fn recv(&mut self) -> Option<(usize, T)> {
+        self.shared.recv(pop).await
+    }
+
+    pub fn try_recv(&mut self) -> Result<(usize, T), TryRecvError> {
+        self.shared.try_recv(pop)
+    }
+
+    /// Number of values queued in a lane.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the lane does not exist.
+    #[must_use]
+    pub fn len(&self, lane: usize) -> usize {
+        self.shared.queue().queues[lane].len()
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.shared.queue().queues.iter().all(VecDeque::is_empty)
+    }
+}
+
+fn pop<T>(state: &State, lanes: &mut Lanes<T>) -> Option<(usize, T)> {
+    let lane = match &state.dequeue {
+        Dequeue::Strict => lanes.queues.iter().position(|queue| !queue.is_empty()),
+        Dequeue::Weighted(weights) => {
+            let Lanes { queues, credits } = lanes;
+            let next = |credits: &[u32]| {
+                queues
+                    .iter()
+                    .zip(credits)
+                    .position(|(queue, credit)| !queue.is_empty() && *credit > 0)
+            };
+            let lane = next(credits).or_else(|| {
+                // Start a new round.
+                credits.clone_from(weights);
+                next(credits)
+            });
+            if let Some(lane) = lane {
+                credits[lane] -= 1;
+            }
+            lane
+        }
+    }?;
+    let value = lanes.queues[lane].pop_front()?;
+    state.metrics[lane].record_received(1);
+    Some((lane, value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn strict_receives_higher_priority_first() {
+        let (tx, mut rx) = channel("test", [4, 4], Dequeue::Strict);
+        tx.send(1, "data").await.unwrap();
+        tx.send(0, "control").await.unwrap();
+        assert_eq!(tx.metrics(1).len, 1);
+
+        assert_eq!(rx.recv().await, Some((0, "control")));
+        assert_eq!(rx.recv().await, Some((1, "data")));
+        let metrics = tx.metrics(1);
+        assert_eq!((metrics.len, metrics.sent, metrics.received), (0, 1, 1));
+        drop(tx);
+        assert_eq!(rx.recv().await, None);
+    }
+
+    #[tokio::test]
+    async fn weighted_shares_lanes_by_weight() {
+        let (tx, mut rx) = channel("test", [4, 4], Dequeue::Weighted(vec![2, 1]));
+        for i in 0..3 {
+            tx.try_send(0, i).unwrap();
+            tx.try_send(1, i).unwrap();
+        }
+        drop(tx);
+
+        let mut lanes = Vec::new();
+        while let Some((lane, _)) = rx.recv().await {
+            lanes.push(lane);
+        }
+        assert_eq!(lanes, vec![0, 0, 1, 0, 1, 1]);
+    }
+}
@@ -0,0 +1,248 @@
+use std::{
+    collections::VecDeque,
+    panic::Location,
+    sync::{Mutex, MutexGuard, PoisonError},
+    time::Duration,
+};
+
+use tokio::time::{Instant, sleep};
+use tracing::warn;
+
+use super::log_throttle::LogThrottle;
+
+#[derive(thiserror::Error, Debug, PartialEq, Eq)]
+pub enum AcquireError {
+    #[error("Weight {weight} exceeds rate limit {limit}.")]
+    WeightExceedsLimit { weight: u64, limit: u64 },
+}
+
+#[derive(thiserror::Error, Debug, PartialEq, Eq)]
+pub enum TryAcquireError {
+    #[error("Weight {weight} exceeds rate limit {limit}.")]
+    WeightExceedsLimit { weight: u64, limit: u64 },
+    #[error("Rate limit reached.")]
+    RateLimited,
+}
+
+/// Limits the total weight acquired within one or more sliding windows, e.g.
+/// 1200 per minute and 50 per 10 seconds at once, as exchanges commonly
+/// impose on request weights.
+///
+/// Waiting acquirers are served first come, first served, so a heavy request
+/// is not starved by a stream of light ones.
+pub struct RateLimiter {
+    name: String,
+    windows: Vec<Window>,
+    // Held by the acquirer at the head of the queue. Tokio's mutex is fair.
+    queue: tokio::sync::Mutex<()>,
+    log_throttle: LogThrottle,
+}
+
+struct Window {
+    limit: u64,
+    period: Duration,
+    // Weights acquired within the period, oldest first.
+    log: Mutex<WindowLog>,
+}
+
+#[derive(Default)]
+struct WindowLog {
+    acquired: VecDeque<(Instant, u64)>,
+    used: u64,
+}
+
+impl Window {
+    fn log(&self, now: Instant) -> MutexGuard<'_, WindowLog> {
+        let mut log = self.log.lock().unwrap_or_else(PoisonError::into_inner);
+        while let Some(&(at, weight)) = log.acquired.front()
+            && at + self.period <= now
+        {
+            log.acquired.pop_front();
+            log.used -= weight;
+        }
+        log
+    }
+
+    // Time until `weight` fits into the window.
+    fn wait(&self, weight: u64, now: Instant) -> Duration {
+        let log = self.log(now);
+        let mut used = log.used;
+        for &(at, acquired) in &log.acquired {
+            if used + weight <= self.limit {
+                break;
+            }
+            used -= acquired;
+            if used + weight <= self.limit {
+                return at + self.period - now;
+            }
+        }
+        Duration::ZERO
+    }
+}
+
+impl RateLimiter {
+    /// Creates a rate limiter allowing a total weight of `limit` per `period`
+    /// for each of the windows.
+    ///
+    /// # Panics
+    ///
+    /// Panics if there are no windows or any limit or period is 0.
+    #[must_use]
+    pub fn new(
+        name: impl Into<String>,
+        windows: impl IntoIterator<Item = (u64, Duration)>,
+    ) -> Self {
+        let windows = windows
+            .into_iter()
+            .map(|(limit, period)| {
+                assert!(limit > 0, "rate limiter requires limit > 0");
+                assert!(!period.is_zero(), "rate limiter requires period > 0");
+                Window {
+                    limit,
+                    period,
+                    log: Mutex::default(),
+                }
+            })
+            .collect::<Vec<_>>();
+        assert!(!windows.is_empty(), "rate limiter requires a window");
+        Self {
+            name: name.into(),
+            windows,
+            queue: tokio::sync::Mutex::new(()),
+            log_throttle: LogThrottle::default(),
+        }
+    }
+
+    #[must_use]
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Waits until `weight` fits into every window and acquires it.
+    ///
+    /// Logs when it has to wait, at most once per second per rate limiter.
+    ///
+    /// Cancel safe: weight is only acquired when the future completes, and a
+    /// cancelled acquirer gives up its place in the queue.
+    #[track_caller]
+    pub fn acquire(&self, weight: u64) -> impl Future<Output = Result<(), AcquireError>> {
+        let caller = Location::caller();
+        async move {
+            self.check_weight(weight)
+                .map_err(|(weight, limit)| AcquireError::WeightExceedsLimit { weight, limit })?;
+
+            let _queue = self.queue.lock().await;
+            let mut logged = false;
+            loop {
+                let now = Instant::now();
+                let wait = self.wait(weight, now);
+                if wait.is_zero() {
+                    self.record(weight, now);
+                    return Ok(());
+                }
+
+                if !logged && let Some(suppressed) = self.log_throttle.check() {
+                    warn!(
+                        rate_limiter = self.name,
+                        weight = weight,
+                        remaining = self.remaining(),
+                        wait = ?wait,
+                        suppressed = suppressed,
+                        file = caller.file(),
+                        line = caller.line(),
+                        "Rate limit reached in rate limiter acquire."
+                    );
+                }
+                logged = true;
+                sleep(wait).await;
+            }
+        }
+    }
+
+    /// Acquires `weight` if it fits into every window and no other acquirer
+    /// is waiting.
+    pub fn try_acquire(&self, weight: u64) -> Result<(), TryAcquireError> {
+        self.check_weight(weight)
+            .map_err(|(weight, limit)| TryAcquireError::WeightExceedsLimit { weight, limit })?;
+
+        let Ok(_queue) = self.queue.try_lock() else {
+            return Err(TryAcquireError::RateLimited);
+        };
+        let now = Instant::now();
+        if !self.wait(weight, now).is_zero() {
+            return Err(TryAcquireError::RateLimited);
+        }
+        self.record(weight, now);
+        Ok(())
+    }
+
+    /// Weight that can currently be acquired without waiting, i.e. the least
+    /// remaining weight across the windows.
+    #[must_use]
+    pub fn remaining(&self) -> u64 {
+        let now = Instant::now();
+        self.windows
+            .iter()
+            .map(|window| window.limit.saturating_sub(window.log(now).used))
+            .min()
+            .unwrap_or(0)
+    }
+
+    fn check_weight(&self, weight: u64) -> Result<(), (u64, u64)> {
+        match self.windows.iter().find(|window| weight > window.limit) {
+            Some(window) => Err((weight, window.limit)),
+            None => Ok(()),
+        }
+    }
+
+    fn wait(&self, weight: u64, now: Instant) -> Duration {
+        self.windows
+            .iter()
+            .map(|window| window.wait(weight, now))
+            .max()
+            .unwrap_or_default()
+    }
+
+    fn record(&self, weight: u64, now: Instant) {
+        for window in &self.windows {
+            let mut log = window.log(now);
+            log.acquired.push_back((now, weight));
+            log.used += weight;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test(start_paused = true)]
+    async fn acquire_waits_for_every_window() {
+        let limiter = RateLimiter::new(
+            "test",
+            [(3, Duration::from_secs(60)), (2, Duration::from_secs(10))],
+        );
+        let start = Instant::now();
+        limiter.acquire(1).await.unwrap();
+        limiter.acquire(1).await.unwrap();
+        assert_eq!(limiter.remaining(), 0);
+        assert_eq!(limiter.try_acquire(1), Err(TryAcquireError::RateLimited));
+
+        limiter.acquire(1).await.unwrap();
+        assert_eq!(start.elapsed(), Duration::from_secs(10));
+        limiter.acquire(1).await.unwrap();
+        assert_eq!(start.elapsed(), Duration::from_secs(60));
+    }
+
+    #[tokio::test]
+    async fn acquire_fails_for_weight_over_limit() {
+        let limiter = RateLimiter::new("test", [(5, Duration::from_secs(1))]);
+        assert_eq!(
+            limiter.acquire(6).await,
+            Err(AcquireError::WeightExceedsLimit {
+                weight: 6,
+                limit: 5
+            })
+        );
+    }
+}
@@ -0,0 +1,93 @@
+use std::{collections::BTreeSet, time::Duration};
+
+use tokio::{sync::watch, time::timeout};
+
+#[derive(thiserror::Error, Debug, PartialEq, Eq)]
+#[error("Timed out waiting for {pending:?} to become ready.")]
+pub struct NotReady {
+    pub pending: Vec<String>,
+}
+
+/// A set of named readiness flags which can be awaited together, e.g. one per
+/// websocket subscription that must be confirmed before trading starts.
+///
+/// Flags can go back to pending, e.g. when a connection drops, so the gate can
+/// also be used to check readiness after startup.
+pub struct ReadyGate {
+    pending: watch::Sender<BTreeSet<String>>,
+}
+
+impl ReadyGate {
+    /// Creates a gate with the given flags, all pending.
+    #[must_use]
+    pub fn new<I>(names: I) -> Self
+    where
+        I: IntoIterator<Item: Into<String>>,
+    {
+        Self {
+            pending: watch::Sender::new(names.into_iter().map(Into::into).collect()),
+        }
+    }
+
+    /// Marks the flag as ready. Returns `false` if it was not pending.
+    pub fn set_ready(&self, name: &str) -> bool {
+        self.pending
+            .send_if_modified(|pending| pending.remove(name))
+    }
+
+    /// Marks the flag as pending, adding it if the gate does not know it yet.
+    /// Returns `false` if it was already pending.
+    pub fn set_pending(&self, name: impl Into<String>) -> bool {
+        let name = name.into();
+        self.pending
+            .send_if_modified(|pending| pending.insert(name))
+    }
+
+    #[must_use]
+    pub fn is_ready(&self) -> bool {
+        self.pending.borrow().is_empty()
+    }
+
+    /// Names of the flags which are not ready yet, in alphabetical order.
+    #[must_use]
+    pub fn pending(&self) -> Vec<String> {
+        self.pending.borrow().iter().cloned().collect()
+    }
+
+    /// Waits until all flags are ready. Cancel safe.
+    pub async fn wait(&self) {
+        self.pending
+            .subscribe()
+            .wait_for(BTreeSet::is_empty)
+            .await
+            .expect("sender is owned by the gate");
+    }
+
+    /// Like [`Self::wait`], but gives up after `duration`, reporting the flags
+    /// which are still pending.
+    pub async fn wait_timeout(&self, duration: Duration) -> Result<(), NotReady> {
+        timeout(duration, self.wait()).await.map_err(|_| NotReady {
+            pending: self.pending(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test(start_paused = true)]
+    async fn wait_timeout_reports_pending_flags() {
+        let gate = ReadyGate::new(["trades", "book"]);
+        assert!(gate.set_ready("book"));
+        assert!(!gate.set_ready("book"));
+
+        let error = gate.wait_timeout(Duration::from_secs(1)).await.unwrap_err();
+        assert_eq!(error.pending, vec!["trades".to_owned()]);
+
+        gate.set_ready("trades");
+        gate.wait_timeout(Duration::from_secs(1)).await.unwrap();
+        assert!(gate.set_pending("trades"));
+        assert!(!gate.is_ready());
+    }
+}
@@ -0,0 +1,156 @@
+use std::sync::{
+    Arc, Mutex, MutexGuard, PoisonError,
+    atomic::{AtomicBool, AtomicUsize, Ordering},
+};
+
+use tokio::sync::{Notify, mpsc::error::TryRecvError};
+
+/// Creates the core of a bounded multi-producer, single-consumer channel with
+/// its own queue, i.e. [`overflow`](super::overflow) and
+/// [`priority`](super::priority): the queue `Q` behind a mutex, the sender
+/// count, closing, and the wakeups between senders and receiver. Senders wait
+/// for capacity in each of the `lanes` independently. `state` holds the rest
+/// of the channel, e.g. its name and metrics.
+pub(crate) fn channel<Q, S>(queue: Q, lanes: usize, state: S) -> (Sender<Q, S>, Receiver<Q, S>) {
+    let shared = Arc::new(Shared {
+        state,
+        queue: Mutex::new(queue),
+        senders: AtomicUsize::new(1),
+        receiver_closed: AtomicBool::new(false),
+        value_sent: Notify::new(),
+        value_received: (0..lanes).map(|_| Notify::new()).collect(),
+    });
+    (
+        Sender {
+            shared: shared.clone(),
+        },
+        Receiver { shared },
+    )
+}
+
+struct Shared<Q, S> {
+    state: S,
+    queue: Mutex<Q>,
+    senders: AtomicUsize,
+    receiver_closed: AtomicBool,
+    value_sent: Notify,
+    // Per lane.
+    value_received: Box<[Notify]>,
+}
+
+impl<Q, S> Shared<Q, S> {
+    fn queue(&self) -> MutexGuard<'_, Q> {
+        self.queue.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+pub(crate) struct Sender<Q, S> {
+    shared: Arc<Shared<Q, S>>,
+}
+
+impl<Q, S> Clone for Sender<Q, S> {
+    fn clone(&self) -> Self {
+        self.shared.senders.fetch_add(1, Ordering::Relaxed);
+        Self {
+            shared: self.shared.clone(),
+        }
+    }
+}
+
+impl<Q, S> Drop for Sender<Q, S> {
+    fn drop(&mut self) {
+        if self.shared.senders.fetch_sub(1, Ordering::AcqRel) == 1 {
+            // Wake the receiver so that it observes the channel closing.
+            self.shared.value_sent.notify_one();
+        }
+    }
+}
+
+impl<Q, S> Sender<Q, S> {
+    pub(crate) fn state(&self) -> &S {
+        &self.shared.state
+    }
+
+    pub(crate) fn queue(&self) -> MutexGuard<'_, Q> {
+        self.shared.queue()
+    }
+
+    /// Locks the queue to push to it, or returns `None` if the receiver was
+    /// dropped.
+    pub(crate) fn open_queue(&self) -> Option<MutexGuard<'_, Q>> {
+        let queue = self.shared.queue();
+        (!self.is_closed()).then_some(queue)
+    }
+
+    pub(crate) fn is_closed(&self) -> bool {
+        self.shared.receiver_closed.load(Ordering::Acquire)
+    }
+
+    /// Wakes the receiver after a value was queued.
+    pub(crate) fn notify_sent(&self) {
+        self.shared.value_sent.notify_one();
+    }
+
+    /// Notified when a value is received from the lane, or the receiver is
+    /// dropped.
+    pub(crate) fn value_received(&self, lane: usize) -> &Notify {
+        &self.shared.value_received[lane]
+    }
+}
+
+pub(crate) struct Receiver<Q, S> {
+    shared: Arc<Shared<Q, S>>,
+}
+
+impl<Q, S> Drop for Receiver<Q, S> {
+    fn drop(&mut self) {
+        self.shared.receiver_closed.store(true, Ordering::Release);
+        // Wake senders waiting for capacity so that they observe the channel
+        // closing.
+        for value_received in &self.shared.value_received {
+            value_received.notify_waiters();
+        }
+    }
+}
+
+impl<Q, S> Receiver<Q, S> {
+    pub(crate) fn queue(&self) -> MutexGuard<'_, Q> {
+        self.shared.queue()
+    }
+
+    /// Waits for a value popped by `pop`, or returns `None` once `pop` finds
+    /// none and all senders have been dropped. See [`Self::try_recv`].
+    pub(crate) async fn recv<T>(
+        &self,
+        mut pop: impl FnMut(&S, &mut Q) -> Option<(usize, T)>,
+    ) -> Option<(usize, T)> {
+        loop {
+            match self.try_recv(&mut pop) {
+                Ok(received) => return Some(received),
+                Err(TryRecvError::Disconnected) => return None,
+                Err(TryRecvError::Empty) => self.shared.value_sent.notified().await,
+            }
+        }
+    }
+
+    /// Pops a value with `pop`, which returns it along with its lane, and
+    /// wakes a sender waiting for capacity in that lane.
+    pub(crate) fn try_recv<T>(
+        &self,
+        pop: impl FnOnce(&S, &mut Q) -> Option<(usize, T)>,
+    ) -> Result<(usize, T), TryRecvError> {
+        let shared = &*self.shared;
+        let mut queue = shared.queue();
+        match pop(&shared.state, &mut queue) {
+            Some((lane, value)) => {
+                drop(queue);
+                shared.value_received[lane].notify_one();
+                Ok((lane, value))
+            }
+            // Checked under the queue lock: a sender pushes before it drops,
+            // so no value can be queued once this observes 0 senders.
+            None if shared.senders.load(Ordering::Acquire) == 0 => Err(TryRecvError::Disconnected),
+            None => Err(TryRecvError::Empty),
+        }
+    }
+}
@@ -0,0 +1,178 @@
+use std::{
+    collections::HashMap,
+    hash::Hash,
+    sync::{Mutex, MutexGuard, PoisonError},
+};
+
+use futures_util::{
+    FutureExt,
+    future::{BoxFuture, Shared},
+};
+
+/// Coalesces concurrent calls for the same key into a single future whose
+/// result is shared by all callers, e.g. to fetch a snapshot or token once
+/// when several tasks ask for it at the same time.
+///
+/// Calls are only coalesced while in flight; a call for a key whose previous
+/// call completed starts a new future. If every caller is cancelled, the
+/// future is dropped.
+pub struct SingleFlight<K, V> {
+    calls: Mutex<Calls<K, V>>,
+}
+
+struct Calls<K, V> {
+    next_id: u64,
+    in_flight: HashMap<K, Call<V>>,
+}
+
+struct Call<V> {
+    // Distinguishes the call from later calls for the same key.
+    id: u64,
+    future: Shared<BoxFuture<'static, V>>,
+    callers: usize,
+}
+
+impl<K, V> Default for SingleFlight<K, V> {
+    fn default() -> Self {
+        Self {
+            calls: Mutex::new(Calls {
+                next_id: 0,
+                in_flight: HashMap::new(),
+            }),
+        }
+    }
+}
+
+impl<K, V> SingleFlight<K, V>
+where
+    K: Eq + Hash + Clone,
+    V: Clone + Send + Sync + 'static,
+{
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Runs the future returned by `f` unless a call for the same key is
+    /// already in flight, in which case `f` is not called and the result of
+    /// the call in flight is returned.
+    pub async fn run<F, Fut>(&self, key: K, f: F) -> V
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = V> + Send + 'static,
+    {
+        let (id, future) = {
+            let mut calls = self.calls();
+            let Calls { next_id, in_flight } = &mut *calls;
+            let call = in_flight.entry(key.clone()).or_insert_with(|| {
+                *next_id += 1;
+                Call {
+                    id: *next_id,
+                    future: f().boxed().shared(),
+                    callers: 0,
+                }
+            });
+            call.callers += 1;
+            (call.id, call.future.clone())
+        };
+
+        let mut caller = Caller {
+            single_flight: self,
+            key,
+            id,
+            completed: false,
+        };
+        let value = future.await;
+        caller.completed = true;
+        value
+    }
+
+    /// Number of keys with a call in flight.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.calls().in_flight.len()
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl<K, V> SingleFlight<K, V> {
+    fn calls(&self) -> MutexGuard<'_, Calls<K, V>> {
+        self.calls.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+// Removes the call once it completed or all its callers were cancelled.
+struct Caller<'a, K, V>
+where
+    K: Eq + Hash,
+{
+    single_flight: &'a SingleFlight<K, V>,
+    key: K,
+    id: u64,
+    completed: bool,
+}
+
+impl<K, V> Drop for Caller<'_, K, V>
+where
+    K: Eq + Hash,
+{
+    fn drop(&mut self) {
+        let mut calls = self.single_flight.calls();
+        if let Some(call) = calls.in_flight.get_mut(&self.key)
+            && call.id == self.id
+        {
+            call.callers -= 1;
+            if self.completed || call.callers == 0 {
+                calls.in_flight.remove(&self.key);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::atomic::{AtomicUsize, Ordering},
+        time::Duration,
+    };
+
+    use tokio::time::{sleep, timeout};
+
+    use super::*;
+
+    #[tokio::test(start_paused = true)]
+    async fn run_coalesces_concurrent_calls() {
+        let single_flight = SingleFlight::new();
+        let calls = AtomicUsize::new(0);
+        let call = || {
+            calls.fetch_add(1, Ordering::Relaxed);
+            async {
+                sleep(Duration::from_secs(1)).await;
+                42
+            }
+        };
+
+        let (a, b) = tokio::join!(single_flight.run("a", call), single_flight.run("a", call));
+        assert_eq!((a, b), (42, 42));
+        assert_eq!(calls.load(Ordering::Relaxed), 1);
+        assert!(single_flight.is_empty());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn run_cleans_up_when_cancelled() {
+        let single_flight = SingleFlight::new();
+        let call = || async {
+            sleep(Duration::from_secs(1)).await;
+            42
+        };
+
+        let cancelled = timeout(Duration::from_millis(1), single_flight.run("a", call)).await;
+        assert!(cancelled.is_err());
+        assert!(single_flight.is_empty());
+        assert_eq!(single_flight.run("a", call).await, 42);
+    }
+}
@@ -0,0 +1,139 @@
+use std::time::Duration;
+
+use futures_util::{Stream, stream};
+use tokio::{
+    sync::watch::{Receiver, Ref},
+    time::Instant,
+};
+
+#[derive(thiserror::Error, Debug, PartialEq, Eq)]
+pub enum WaitForTimeoutError {
+    #[error("Watch sender was dropped.")]
+    Closed,
+    #[error("Timed out waiting for watch value.")]
+    Timeout,
+}
+
+pub trait ReceiverExt<T> {
+    /// Waits for a value that satisfies the predicate, like
+    /// [`Receiver::wait_for`], but gives up after `timeout`.
+    fn wait_for_timeout<'a>(
+        &'a mut self,
+        timeout: Duration,
+        f: impl FnMut(&T) -> bool,
+    ) -> impl Future<Output = Result<Ref<'a, T>, WaitForTimeoutError>>
+    where
+        T: 'a;
+
+    /// Converts the receiver into a stream yielding the latest value on every
+    /// change. Ends once the sender is dropped.
+    fn into_changes(self) -> impl Stream<Item = T>
+    where
+        T: Clone;
+
+    /// Like [`Self::into_changes`], but only yields once no further change
+    /// has happened for `quiet`.
+    fn into_debounced_changes(self, quiet: Duration) -> impl Stream<Item = T>
+    where
+        T: Clone;
+
+    /// Like [`Self::into_changes`], but yields at most once per `period`. The
+    /// first change is yielded immediately; changes within the period are
+    /// coalesced into the latest value.
+    fn into_throttled_changes(self, period: Duration) -> impl Stream<Item = T>
+    where
+        T: Clone;
+}
+
+impl<T> ReceiverExt<T> for Receiver<T> {
+    async fn wait_for_timeout<'a>(
+        &'a mut self,
+        timeout: Duration,
+        f: impl FnMut(&T) -> bool,
+    ) -> Result<Ref<'a, T>, WaitForTimeoutError>
+    where
+        T: 'a,
+    {
+        match tokio::time::timeout(timeout, self.wait_for(f)).await {
+            Ok(Ok(value)) => Ok(value),
+            Ok(Err(_)) => Err(WaitForTimeoutError::Closed),
+            Err(_) => Err(WaitForTimeoutError::Timeout),
+        }
+    }
+
+    fn into_changes(self) -> impl Stream<Item = T>
+    where
+        T: Clone,
+    {
+        stream::unfold(self, |mut rx| async move {
+            rx.changed().await.ok()?;
+            let value = rx.borrow_and_update().clone();
+            Some((value, rx))
+        })
+    }
+
+    fn into_debounced_changes(self, quiet: Duration) -> impl Stream<Item = T>
+    where
+        T: Clone,
+    {
+        stream::unfold(self, move |mut rx| async move {
+            rx.changed().await.ok()?;
+            // Keep waiting while changes keep coming. If the sender is dropped
+            // in the meantime, still yield the last value.
+            while let Ok(Ok(())) = tokio::time::timeout(quiet, rx.changed()).await {}
+            let value = rx.borrow_and_update().clone();
+            Some((value, rx))
+        })
+    }
+
+    fn into_throttled_changes(self, period: Duration) -> impl Stream<Item = T>
+    where
+        T: Clone,
+    {
+        stream::unfold((self, None), move |(mut rx, not_before)| async move {
+            rx.changed().await.ok()?;
+            if let Some(not_before) = not_before {
+                tokio::time::sleep_until(not_before).await;
+            }
+            let value = rx.borrow_and_update().clone();
+            Some((value, (rx, Some(Instant::now() + period))))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures_util::StreamExt;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn wait_for_timeout_times_out() {
+        // Does not require the value to be `Clone`.
+        struct Value(u32);
+
+        let (tx, mut rx) = tokio::sync::watch::channel(Value(0));
+        assert!(matches!(
+            rx.wait_for_timeout(Duration::from_millis(1), |value| value.0 > 0)
+                .await,
+            Err(WaitForTimeoutError::Timeout)
+        ));
+        drop(tx);
+        assert!(matches!(
+            rx.wait_for_timeout(Duration::from_millis(1), |value| value.0 > 0)
+                .await,
+            Err(WaitForTimeoutError::Closed)
+        ));
+    }
+
+    #[tokio::test]
+    async fn debounced_changes_yield_latest_value() {
+        let (tx, rx) = tokio::sync::watch::channel(0);
+        let changes = rx.into_debounced_changes(Duration::from_millis(1));
+        tx.send(1).unwrap();
+        tx.send(2).unwrap();
+        drop(tx);
+
+        assert_eq!(changes.collect::<Vec<_>>().await, vec![2]);
+    }
+}
@@ -0,0 +1,252 @@
+use std::{
+    io,
+    panic::{AssertUnwindSafe, catch_unwind},
+    pin::Pin,
+    sync::{
+        Arc, Mutex, PoisonError,
+        atomic::{AtomicBool, Ordering},
+        mpsc,
+    },
+    task::{Context, Poll},
+    thread,
+    time::{Duration, Instant},
+};
+
+use tokio::sync::{Semaphore, oneshot};
+use tracing::warn;
+
+use super::panic::payload_message;
+use crate::sync::LogThrottle;
+
+type Job = Box<dyn FnOnce() + Send>;
+
+#[derive(thiserror::Error, Debug)]
+pub enum BlockingError {
+    #[error("Blocking call panicked: {0}")]
+    Panicked(String),
+    #[error("Blocking call was cancelled.")]
+    Cancelled,
+}
+
+/// Result of a call run by a [`BlockingPool`].
+#[derive(Clone, Copy, Debug)]
+pub struct BlockingOutput<T> {
+    pub value: T,
+    /// Time spent waiting in the queue.
+    pub queued: Duration,
+    /// Time spent running.
+    pub duration: Duration,
+}
+
+/// A fixed set of threads for CPU-heavy work, e.g. parsing large JSON
+/// messages or signing requests, which keeps that work off the async workers
+/// and Tokio's blocking pool.
+///
+/// At most `queue_limit` calls wait for a free thread. [`Self::spawn`] waits
+/// for a slot when the queue is full and warns, so producers slow down instead
+/// of growing the queue without bound.
+///
+/// Dropping the pool cancels the queued calls. Calls already running complete
+/// on their threads in the background.
+pub struct BlockingPool {
+    name: String,
+    queue_limit: usize,
+    queue: Arc<Semaphore>,
+    jobs: Option<mpsc::Sender<Job>>,
+    closed: Arc<AtomicBool>,
+    throttle: LogThrottle,
+}
+
+impl BlockingPool {
+    /// Starts `threads` threads named `{name}-{index}`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `threads` or `queue_limit` is 0.
+    pub fn new(name: impl Into<String>, threads: usize, queue_limit: usize) -> io::Result<Self> {
+        assert!(threads > 0, "blocking pool requires threads > 0");
+        assert!(queue_limit > 0, "blocking pool requires queue_limit > 0");
+        let name = name.into();
+        let (jobs, receiver) = mpsc::channel::<Job>();
+        let receiver = Arc::new(Mutex::new(receiver));
+        let closed = Arc::new(AtomicBool::new(false));
+        for index in 0..threads {
+            let receiver = receiver.clone();
+            let closed = closed.clone();
+            thread::Builder::new()
+                .name(format!("{name}-{index}"))
+                .spawn(move || {
+                    loop {
+                        let job = receiver
+                            .lock()
+                            .unwrap_or_else(PoisonError::into_inner)
+                            .recv();
+                        match job {
+                            // Dropping a queued job cancels it.
+                            Ok(job) if !closed.load(Ordering::Relaxed) => job(),
+                            Ok(_) => {}
+                            Err(_) => return,
+                        }
+                    }
+                })?;
+        }
+        Ok(Self {
+            name,
+            queue_limit,
+            queue: Arc::new(Semaphore::new(queue_limit)),
+            jobs: Some(jobs),
+            closed,
+            throttle: LogThrottle::default(),
+        })
+    }
+
+    #[must_use]
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Number of calls waiting for a free thread.
+    #[must_use]
+    pub fn queued(&self) -> usize {
+        self.queue_limit - self.queue.available_permits()
+    }
+
+    /// Queues `f` to run on a pool thread, waiting for a slot if the queue is
+    /// full.
+    ///
+    /// Dropping the returned handle before `f` started cancels the call.
+    pub async fn spawn<F, T>(&self, f: F) -> BlockingHandle<T>
+    where
+        F: FnOnce() -> T + Send + 'static,
+        T: Send + 'static,
+    {
+        let permit = match self.queue.clone().try_acquire_owned() {
+            Ok(permit) => permit,
+            Err(_) => {
+                if let Some(suppressed) = self.throttle.check() {
+                    warn!(
+                        pool = self.name,
+                        queue_limit = self.queue_limit,
+                        suppressed = suppressed,
+                        "Blocking pool queue full."
+                    );
+                }
+                self.queue
+                    .clone()
+                    .acquire_owned()
+                    .await
+                    .expect("semaphore is never closed")
+            }
+        };
+
+        let (tx, rx) = oneshot::channel();
+        let submitted = Instant::now();
+        let job = Box::new(move || {
+            drop(permit);
+            if tx.is_closed() {
+                return;
+            }
+            let queued = submitted.elapsed();
+            let started = Instant::now();
+            let result = catch_unwind(AssertUnwindSafe(f))
+                .map(|value| BlockingOutput {
+                    value,
+                    queued,
+                    duration: started.elapsed(),
+                })
+                .map_err(|payload| payload_message(&*payload));
+            let _ = tx.send(result);
+        });
+        if let Some(jobs) = &self.jobs {
+            // Fails only if every thread is gone, which cancels the call.
+            let _ = jobs.send(job);
+        }
+        BlockingHandle { rx }
+    }
+}
+
+impl Drop for BlockingPool {
+    fn drop(&mut self) {
+        self.closed.store(true, Ordering::Relaxed);
+        // Stops the threads once they drained the queue.
+        drop(self.jobs.take());
+    }
+}
+
+/// Resolves to the result of a call queued on a [`BlockingPool`].
+pub struct BlockingHandle<T> {
+    rx: oneshot::Receiver<Result<BlockingOutput<T>, String>>,
+}
+
+impl<T> BlockingHandle<T> {
+    /// Cancels the call, after which the handle resolves to
+    /// [`BlockingError::Cancelled`]. A queued call does not run; a running
+    /// call completes on its thread, but its result is discarded.
+    pub fn cancel(&mut self) {
+        self.rx.close();
+    }
+}
+
+impl<T> Future for BlockingHandle<T> {
+    type Output = Result<BlockingOutput<T>, BlockingError>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        Pin::new(&mut self.rx).poll(cx).map(|result| match result {
+            Ok(Ok(output)) => Ok(output),
+            Ok(Err(message)) => Err(BlockingError::Panicked(message)),
+            Err(_) => Err(BlockingError::Cancelled),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn spawn_measures_and_cancels_calls() {
+        let pool = BlockingPool::new("blocking", 1, 2).unwrap();
+        let (started_tx, started_rx) = std::sync::mpsc::channel();
+        let (release_tx, release_rx) = std::sync::mpsc::channel::<()>();
+        let first = pool
+            .spawn(move || {
+                started_tx.send(()).unwrap();
+                release_rx.recv().unwrap();
+                1
+            })
+            .await;
+        started_rx.recv().unwrap();
+        let mut second = pool.spawn(|| 2).await;
+        let third = pool.spawn(|| panic!("boom")).await;
+        assert_eq!(pool.queued(), 2);
+
+        second.cancel();
+        release_tx.send(()).unwrap();
+        assert_eq!(first.await.unwrap().value, 1);
+        assert!(matches!(second.await, Err(BlockingError::Cancelled)));
+        assert!(matches!(third.await, Err(BlockingError::Panicked(m)) if m == "boom"));
+    }
+
+    #[tokio::test]
+    async fn cancel_discards_result_of_running_call() {
+        let pool = BlockingPool::new("blocking", 1, 1).unwrap();
+        let (started_tx, started_rx) = std::sync::mpsc::channel();
+        let (release_tx, release_rx) = std::sync::mpsc::channel::<()>();
+        let (done_tx, done_rx) = std::sync::mpsc::channel();
+        let mut handle = pool
+            .spawn(move || {
+                started_tx.send(()).unwrap();
+                release_rx.recv().unwrap();
+                done_tx.send(()).unwrap();
+                1
+            })
+            .await;
+        started_rx.recv().unwrap();
+
+        handle.cancel();
+        release_tx.send(()).unwrap();
+        assert!(matches!(handle.await, Err(BlockingError::Cancelled)));
+        // The call still ran to completion.
+        done_rx.recv().unwrap();
+    }
+}
@@ -0,0 +1,265 @@
+use std::{sync::Arc, time::Duration};
+
+use futures_util::{Stream, StreamExt, stream};
+use tokio::{
+    sync::{OwnedSemaphorePermit, Semaphore},
+    task::{JoinError, JoinHandle},
+    time::{sleep, timeout},
+};
+use tracing::warn;
+
+use super::{JoinSet, spawn_named};
+use crate::sync::LogThrottle;
+
+#[derive(thiserror::Error, Debug)]
+pub enum BoundedError<E> {
+    #[error("Task timed out.")]
+    Timeout,
+    #[error("Task panicked.")]
+    Panicked(#[source] JoinError),
+    #[error("Task was cancelled.")]
+    Cancelled(#[source] JoinError),
+    #[error("Task returned an error.")]
+    Err(#[source] E),
+}
+
+/// Spawns at most `limit` tasks at once, e.g. to fan out REST calls without
+/// overwhelming the server or growing memory with the number of calls.
+///
+/// [`Self::spawn`] waits for a free slot before spawning. [`Self::run_ordered`]
+/// and [`Self::run_unordered`] pull items lazily, so only up to `limit` items
+/// are in flight while the caller consumes the results. All of them share the
+/// limit of the spawner.
+pub struct BoundedSpawner {
+    name: String,
+    limit: usize,
+    semaphore: Arc<Semaphore>,
+    timeout: Option<Duration>,
+    retries: u32,
+    retry_delay: Duration,
+    retry_log_throttle: LogThrottle,
+}
+
+impl BoundedSpawner {
+    /// # Panics
+    ///
+    /// Panics if `limit` is 0.
+    #[must_use]
+    pub fn new(name: impl Into<String>, limit: usize) -> Self {
+        assert!(limit > 0, "bounded spawner requires limit > 0");
+        Self {
+            name: name.into(),
+            limit,
+            semaphore: Arc::new(Semaphore::new(limit)),
+            timeout: None,
+            retries: 0,
+            retry_delay: Duration::ZERO,
+            retry_log_throttle: LogThrottle::default(),
+        }
+    }
+
+    /// Sets a timeout per attempt of an item run by [`Self::run_ordered`] or
+    /// [`Self::run_unordered`].
+    #[must_use]
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Retries an item run by [`Self::run_ordered`] or [`Self::run_unordered`]
+    /// up to `retries` times after `delay` if it returns an error, times out or
+    /// panics. Retries are logged at most once per second per spawner.
+    #[must_use]
+    pub fn retries(mut self, retries: u32, delay: Duration) -> Self {
+        self.retries = retries;
+        self.retry_delay = delay;
+        self
+    }
+
+    #[must_use]
+    pub fn limit(&self) -> usize {
+        self.limit
+    }
+
+    /// Number of tasks currently running.
+    #[must_use]
+    pub fn running(&self) -> usize {
+        self.limit - self.semaphore.available_permits()
+    }
+
+    /// Waits until fewer than `limit` tasks are running and spawns the future
+    /// with [`spawn_named`].
+    ///
+    /// # Panics
+    ///
+    /// This method panics if called outside of a Tokio runtime.
+    pub async fn spawn<F>(&self, future: F) -> JoinHandle<F::Output>
+    where
+        F: Future + Send + 'static,
+        F::Output: Send + 'static,
+    {
+        let permit = self.acquire().await;
+        spawn_named(&self.name, async move {
+            let _permit = permit;
+            future.await
+        })
+    }
+
+    /// Runs the future returned by `f` for every item and yields the results in
+    /// input order.
+    ///
+    /// Dropping the stream aborts the tasks in flight.
+    pub fn run_ordered<I, F, Fut, T, E>(
+        &self,
+        items: I,
+        f: F,
+    ) -> impl Stream<Item = Result<T, BoundedError<E>>>
+    where
+        I: IntoIterator<Item: Clone>,
+        F: Fn(I::Item) -> Fut,
+        Fut: Future<Output = Result<T, E>> + Send + 'static,
+        T: Send + 'static,
+        E: Send + 'static,
+    {
+        let f = Arc::new(f);
+        stream::iter(items)
+            .map(move |item| self.run(item, f.clone()))
+            .buffered(self.limit)
+    }
+
+    /// Runs the future returned by `f` for every item and yields the results
+    /// along with the index of their item in completion order.
+    ///
+    /// Dropping the stream aborts the tasks in flight.
+    pub fn run_unordered<I, F, Fut, T, E>(
+        &self,
+        items: I,
+        f: F,
+    ) -> impl Stream<Item = (usize, Result<T, BoundedError<E>>)>
+    where
+        I: IntoIterator<Item: Clone>,
+        F: Fn(I::Item) -> Fut,
+        Fut: Future<Output = Result<T, E>> + Send + 'static,
+        T: Send + 'static,
+        E: Send + 'static,
+    {
+        let f = Arc::new(f);
+        stream::iter(items)
+            .enumerate()
+            .map(move |(index, item)| {
+                let run = self.run(item, f.clone());
+                async move { (index, run.await) }
+            })
+            .buffer_unordered(self.limit)
+    }
+
+    async fn acquire(&self) -> OwnedSemaphorePermit {
+        self.semaphore
+            .clone()
+            .acquire_owned()
+            .await
+            .expect("semaphore is never closed")
+    }
+
+    async fn run<A, F, Fut, T, E>(&self, item: A, f: Arc<F>) -> Result<T, BoundedError<E>>
+    where
+        A: Clone,
+        F: Fn(A) -> Fut,
+        Fut: Future<Output = Result<T, E>> + Send + 'static,
+        T: Send + 'static,
+        E: Send + 'static,
+    {
+        let mut attempt = 0;
+        loop {
+            let permit = self.acquire().await;
+            let future = f(item.clone());
+            let limit = self.timeout;
+            // Aborts the task if the stream is dropped.
+            let mut task = JoinSet::new();
+            task.insert(spawn_named(&self.name, async move {
+                let _permit = permit;
+                match limit {
+                    Some(limit) => timeout(limit, future).await.ok(),
+                    None => Some(future.await),
+                }
+            }));
+
+            let error = match task.join_next().await.map(|(_, result)| result) {
+                Some(Ok(Some(Ok(value)))) => return Ok(value),
+                Some(Ok(Some(Err(e)))) => BoundedError::Err(e),
+                Some(Ok(None)) => BoundedError::Timeout,
+                Some(Err(e)) if e.is_panic() => BoundedError::Panicked(e),
+                Some(Err(e)) => BoundedError::Cancelled(e),
+                None => unreachable!("task was inserted"),
+            };
+            if attempt == self.retries {
+                return Err(error);
+            }
+            attempt += 1;
+            if let Some(suppressed) = self.retry_log_throttle.check() {
+                warn!(
+                    spawner = self.name,
+                    attempt = attempt,
+                    error = %error,
+                    suppressed = suppressed,
+                    "Retrying bounded task."
+                );
+            }
+            sleep(self.retry_delay).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::*;
+
+    #[tokio::test(start_paused = true)]
+    async fn run_ordered_limits_concurrency() {
+        let spawner = BoundedSpawner::new("test", 2);
+        let running = Arc::new(AtomicUsize::new(0));
+        let peak = Arc::new(AtomicUsize::new(0));
+        let results = spawner
+            .run_ordered(0..5u64, |i| {
+                let running = running.clone();
+                let peak = peak.clone();
+                async move {
+                    let now = running.fetch_add(1, Ordering::Relaxed) + 1;
+                    peak.fetch_max(now, Ordering::Relaxed);
+                    // Later items finish first.
+                    sleep(Duration::from_secs(10 - i)).await;
+                    running.fetch_sub(1, Ordering::Relaxed);
+                    Ok::<_, ()>(i)
+                }
+            })
+            .map(Result::unwrap)
+            .collect::<Vec<_>>()
+            .await;
+
+        assert_eq!(results, vec![0, 1, 2, 3, 4]);
+        assert_eq!(peak.load(Ordering::Relaxed), 2);
+    }
+
+    #[tokio::test]
+    async fn run_unordered_retries_errors() {
+        let spawner = BoundedSpawner::new("test", 2).retries(1, Duration::ZERO);
+        let attempts = Arc::new(AtomicUsize::new(0));
+        let results = spawner
+            .run_unordered([()], |()| {
+                let attempt = attempts.fetch_add(1, Ordering::Relaxed);
+                async move {
+                    if attempt == 0 {
+                        Err("boom")
+                    } else {
+                        Ok(attempt)
+                    }
+                }
+            })
+            .collect::<Vec<_>>()
+            .await;
+
+        assert!(matches!(results.as_slice(), [(0, Ok(1))]));
+    }
+}
@@ -0,0 +1,440 @@
+use std::{
+    convert::Infallible,
+    future::poll_fn,
+    mem,
+    pin::Pin,
+    task::{Context, Poll, Waker, ready},
+};
+
+use futures_util::{Stream, StreamExt, stream::FuturesUnordered};
+use std_ext::result::ResultExt;
+use tokio::task::{JoinError, JoinHandle};
+
+/// A set of [`tokio::task::JoinHandle`]s similar to [`tokio::task::JoinSet`],
+/// but with a key difference: tasks must be spawned separately before their
+/// handles can be added to this set.
+///
+/// This struct provides a way to manage multiple asynchronous tasks by
+/// collecting their [`tokio::task::JoinHandle`]s. Unlike
+/// [`tokio::task::JoinSet`], which spawns tasks and adds their handles
+/// directly, this `JoinSet` requires you to spawn tasks externally and then
+/// insert the resulting handles into the set manually.
+///
+/// Every task is identified by a key of type `K`, which is either assigned by
+/// [`Self::insert`] or given to [`Self::insert_with_key`]. Tasks still running
+/// when the set is dropped are aborted.
+///
+/// Only tasks which were woken are polled, so joining all of `n` tasks takes
+/// `O(n)` polls.
+pub struct JoinSet<T, K = usize> {
+    tasks: FuturesUnordered<Task<T, K>>,
+    // Number of tasks which were neither joined nor removed. `tasks` may also
+    // hold removed tasks until they are polled once more.
+    len: usize,
+    next_key: usize,
+    next_seq: u64,
+}
+
+// Insertion order, key and result of a joined task.
+type Joined<T, K> = (u64, K, Result<T, JoinError>);
+
+struct Task<T, K> {
+    // Insertion order.
+    seq: u64,
+    key: Option<K>,
+    // `None` once joined or removed.
+    handle: Option<JoinHandle<T>>,
+    // Wakes the task inside `FuturesUnordered` so that it is released after
+    // its handle was removed.
+    waker: Option<Waker>,
+}
+
+// Nothing is structurally pinned.
+impl<T, K> Unpin for Task<T, K> {}
+
+impl<T, K> Future for Task<T, K> {
+    type Output = Option<Joined<T, K>>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        let Some(handle) = &mut this.handle else {
+            return Poll::Ready(None);
+        };
+        match Pin::new(handle).poll(cx) {
+            Poll::Ready(result) => {
+                this.handle = None;
+                let key = this.key.take().expect("key is taken with the handle");
+                Poll::Ready(Some((this.seq, key, result)))
+            }
+            Poll::Pending => {
+                if !this.waker.as_ref().is_some_and(|w| w.will_wake(cx.waker())) {
+                    this.waker = Some(cx.waker().clone());
+                }
+                Poll::Pending
+            }
+        }
+    }
+}
+
+impl<T, K> Default for JoinSet<T, K> {
+    fn default() -> Self {
+        Self {
+            tasks: FuturesUnordered::new(),
+            len: 0,
+            next_key: 0,
+            next_seq: 0,
+        }
+    }
+}
+
+impl<T> JoinSet<T> {
+    /// Inserts a task and returns its key. Keys are assigned in insertion
+    /// order, starting at 0.
+    pub fn insert(&mut self, handle: JoinHandle<T>) -> usize {
+        let key = self.next_key;
+        self.next_key += 1;
+        self.insert_with_key(key, handle);
+        key
+    }
+}
+
+impl<T, K> JoinSet<T, K> {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Inserts a task under the given key. Keys need not be unique, but
+    /// [`Self::remove`] and [`Self::abort`] only act on the first task with
+    /// the key.
+    pub fn insert_with_key(&mut self, key: K, handle: JoinHandle<T>) {
+        self.tasks.push(Task {
+            seq: self.next_seq,
+            key: Some(key),
+            handle: Some(handle),
+            waker: None,
+        });
+        self.next_seq += 1;
+        self.len += 1;
+    }
+
+    /// Number of tasks in the set, including finished tasks whose results have
+    /// not been joined yet.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Removes a task from the set without aborting it.
+    pub fn remove(&mut self, key: &K) -> Option<JoinHandle<T>>
+    where
+        K: PartialEq,
+    {
+        let task = self
+            .tasks
+            .iter_mut()
+            .filter(|task| task.handle.is_some() && task.key.as_ref() == Some(key))
+            .min_by_key(|task| task.seq)?;
+        task.key = None;
+        let handle = task.handle.take();
+        if let Some(waker) = task.waker.take() {
+            waker.wake();
+        }
+        self.len -= 1;
+        handle
+    }
+
+    /// Aborts a task. Its result is still returned by [`Self::join_next`],
+    /// usually as a cancelled [`JoinError`].
+    pub fn abort(&mut self, key: &K) -> bool
+    where
+        K: PartialEq,
+    {
+        let task = self
+            .tasks
+            .iter()
+            .filter(|task| task.key.as_ref() == Some(key))
+            .min_by_key(|task| task.seq);
+        match task.and_then(|task| task.handle.as_ref()) {
+            Some(handle) => {
+                handle.abort();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Aborts every task. Their results are still returned by
+    /// [`Self::join_next`].
+    pub fn abort_all(&mut self) {
+        for handle in self.tasks.iter().filter_map(|task| task.handle.as_ref()) {
+            handle.abort();
+        }
+    }
+
+    /// Waits for the next task to finish and returns its key and result, or
+    /// `None` if the set is empty.
+    ///
+    /// Cancel safe: if this future is dropped, no task result is lost.
+    pub async fn join_next(&mut self) -> Option<(K, Result<T, JoinError>)> {
+        self.next().await
+    }
+
+    pub fn poll_join_next(
+        &mut self,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<(K, Result<T, JoinError>)>> {
+        self.poll_join_next_seq(cx)
+            .map(|joined| joined.map(|(_, key, result)| (key, result)))
+    }
+
+    fn poll_join_next_seq(&mut self, cx: &mut Context<'_>) -> Poll<Option<Joined<T, K>>> {
+        while self.len > 0 {
+            match ready!(self.tasks.poll_next_unpin(cx)) {
+                Some(Some(joined)) => {
+                    self.len -= 1;
+                    return Poll::Ready(Some(joined));
+                }
+                // A removed task.
+                Some(None) => {}
+                None => break,
+            }
+        }
+        Poll::Ready(None)
+    }
+
+    // Joins every task, stopping at the first result `f` maps to an error,
+    // and returns the mapped results in insertion order.
+    async fn join_all_with<U, E>(
+        &mut self,
+        mut f: impl FnMut(K, Result<T, JoinError>) -> Result<U, E>,
+    ) -> Result<Vec<U>, E> {
+        let mut results = Vec::with_capacity(self.len);
+        while let Some((seq, key, result)) = poll_fn(|cx| self.poll_join_next_seq(cx)).await {
+            results.push((seq, f(key, result)?));
+        }
+        results.sort_unstable_by_key(|(seq, _)| *seq);
+        Ok(results.into_iter().map(|(_, value)| value).collect())
+    }
+
+    /// Awaits completion of every inserted task and returns the results in
+    /// insertion order.
+    ///
+    /// If this future is dropped before completion, any still-unfinished
+    /// tasks are aborted via the [`JoinSet`]'s `Drop` impl.
+    pub async fn join_all(self) -> Vec<Result<T, JoinError>> {
+        // Hold the set so `Drop` still observes the handles if the caller
+        // cancels this future mid-await.
+        let mut this = self;
+        let Ok(results) = this
+            .join_all_with(|_, result| Ok::<_, Infallible>(result))
+            .await;
+        results
+    }
+
+    /// Awaits completion of every inserted task, short-circuiting on the
+    /// first error.
+    ///
+    /// If this future is dropped before completion, any still-unfinished
+    /// tasks are aborted via the [`JoinSet`]'s `Drop` impl.
+    pub async fn try_join_all(self) -> Result<Vec<T>, JoinError> {
+        let mut this = self;
+        this.join_all_with(|_, result| result).await
+    }
+
+    /// Removes every task from the set without aborting it, in insertion
+    /// order.
+    pub fn drain(&mut self) -> Vec<JoinHandle<T>> {
+        let mut handles = mem::take(&mut self.tasks)
+            .into_iter()
+            .filter_map(|task| Some((task.seq, task.handle?)))
+            .collect::<Vec<_>>();
+        self.len = 0;
+        handles.sort_unstable_by_key(|(seq, _)| *seq);
+        handles.into_iter().map(|(_, handle)| handle).collect()
+    }
+}
+
+impl<T, E, K> JoinSet<Result<T, E>, K> {
+    /// Awaits completion of every inserted task, short-circuiting on the
+    /// first task which panicked, was cancelled or returned an error.
+    ///
+    /// On failure, every remaining task is aborted and the key of the failed
+    /// task is returned along with the reason.
+    pub async fn try_join_all_flatten(self) -> Result<Vec<T>, TryJoinError<K, E>> {
+        let mut this = self;
+        // Remaining tasks are aborted when `this` is dropped.
+        this.join_all_with(|key, result| {
+            result
+                .map(|result| result.map_err(TaskFailure::Err))
+                .flatten_into::<TaskFailure<E>>()
+                .map_err(|failure| TryJoinError { key, failure })
+        })
+        .await
+    }
+}
+
+/// Why a task failed.
+#[derive(thiserror::Error, Debug)]
+pub enum TaskFailure<E> {
+    #[error("Task panicked.")]
+    Panicked(#[source] JoinError),
+    #[error("Task was cancelled.")]
+    Cancelled(#[source] JoinError),
+    #[error("Task returned an error.")]
+    Err(#[source] E),
+}
+
+impl<E> From<JoinError> for TaskFailure<E> {
+    fn from(e: JoinError) -> Self {
+        if e.is_panic() {
+            Self::Panicked(e)
+        } else {
+            Self::Cancelled(e)
+        }
+    }
+}
+
+/// Returned by [`JoinSet::try_join_all_flatten`] for the first failed task.
+#[derive(thiserror::Error, Debug)]
+#[error("Task {key:?} failed.")]
+pub struct TryJoinError<K, E> {
+    pub key: K,
+    #[source]
+    pub failure: TaskFailure<E>,
+}
+
+impl<T, K> Drop for JoinSet<T, K> {
+    fn drop(&mut self) {
+        self.abort_all();
+    }
+}
+
+impl<T, K> Stream for JoinSet<T, K> {
+    type Item = (K, Result<T, JoinError>);
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.get_mut().poll_join_next(cx)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.len, Some(self.len))
+    }
+}
+
+impl<T> FromIterator<JoinHandle<T>> for JoinSet<T> {
+    fn from_iter<I: IntoIterator<Item = JoinHandle<T>>>(iter: I) -> Self {
+        let mut join_set = Self::new();
+        for handle in iter {
+            join_set.insert(handle);
+        }
+        join_set
+    }
+}
+
+impl<T, K> FromIterator<(K, JoinHandle<T>)> for JoinSet<T, K> {
+    fn from_iter<I: IntoIterator<Item = (K, JoinHandle<T>)>>(iter: I) -> Self {
+        let mut join_set = Self::new();
+        for (key, handle) in iter {
+            join_set.insert_with_key(key, handle);
+        }
+        join_set
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use futures_util::StreamExt;
+    use tokio::sync::oneshot;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn join_set_join_all() {
+        let mut join_set = JoinSet::new();
+
+        // Spawn and insert tasks.
+        for i in 0..3 {
+            join_set.insert(tokio::spawn(async move { i }));
+        }
+
+        // Await all tasks.
+        let results = join_set.join_all().await;
+
+        assert_eq!(
+            results.into_iter().map(Result::unwrap).collect::<Vec<_>>(),
+            vec![0, 1, 2]
+        );
+    }
+
+    #[tokio::test]
+    async fn join_next_yields_in_completion_order() {
+        let (tx, rx) = oneshot::channel::<()>();
+        let mut join_set = JoinSet::new();
+        join_set.insert_with_key("slow", tokio::spawn(async move { rx.await.is_ok() }));
+        join_set.insert_with_key("fast", tokio::spawn(async { true }));
+        join_set.insert_with_key(
+            "aborted",
+            tokio::spawn(async {
+                tokio::time::sleep(Duration::from_secs(60)).await;
+                true
+            }),
+        );
+        assert!(join_set.abort(&"aborted"));
+
+        let mut finished = Vec::new();
+        while finished.len() < 2 {
+            let (key, result) = join_set.join_next().await.unwrap();
+            finished.push((key, result.is_ok()));
+        }
+        finished.sort_unstable();
+        assert_eq!(finished, vec![("aborted", false), ("fast", true)]);
+
+        tx.send(()).unwrap();
+        assert_eq!(join_set.next().await.map(|(key, _)| key), Some("slow"));
+        assert!(join_set.is_empty());
+    }
+
+    #[tokio::test]
+    async fn remove_detaches_pending_task() {
+        let (tx, rx) = oneshot::channel::<()>();
+        let mut join_set = JoinSet::new();
+        let pending = join_set.insert(tokio::spawn(async move { rx.await.is_ok() }));
+        join_set.insert(tokio::spawn(async { true }));
+        // Polls the pending task once.
+        assert_eq!(join_set.join_next().await.map(|(key, _)| key), Some(1));
+
+        let handle = join_set.remove(&pending).unwrap();
+        assert!(join_set.is_empty());
+        assert!(join_set.join_next().await.is_none());
+        tx.send(()).unwrap();
+        assert!(handle.await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn try_join_all_flatten_aborts_siblings_on_err() {
+        let (tx, rx) = oneshot::channel::<()>();
+        let mut join_set = JoinSet::new();
+        join_set.insert_with_key(
+            "pending",
+            tokio::spawn(async move {
+                let _tx = tx;
+                std::future::pending::<Result<(), &str>>().await
+            }),
+        );
+        join_set.insert_with_key("failing", tokio::spawn(async { Err("boom") }));
+
+        let error = join_set.try_join_all_flatten().await.unwrap_err();
+        assert_eq!(error.key, "failing");
+        assert!(matches!(error.failure, TaskFailure::Err("boom")));
+        // The pending task was aborted, dropping the sender.
+        assert!(rx.await.is_err());
+    }
+}
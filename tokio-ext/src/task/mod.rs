@@ -0,0 +1,94 @@
+#[cfg(all(feature = "rt", feature = "sync"))]
+mod blocking;
+#[cfg(all(feature = "rt", feature = "sync"))]
+mod bounded;
+#[cfg(feature = "rt")]
+mod join_set;
+#[cfg(all(feature = "rt", feature = "sync"))]
+mod panic;
+#[cfg(all(feature = "rt", feature = "sync"))]
+mod periodic;
+#[cfg(feature = "rt")]
+mod registry;
+#[cfg(feature = "shutdown")]
+mod shutdown;
+#[cfg(all(feature = "rt", feature = "sync"))]
+mod supervisor;
+
+#[cfg(feature = "rt")]
+pub use self::join_set::*;
+#[cfg(all(feature = "rt", feature = "sync"))]
+pub use self::panic::*;
+#[cfg(all(feature = "rt", feature = "sync"))]
+pub use self::periodic::*;
+#[cfg(feature = "rt")]
+pub use self::registry::{TaskInfo, TaskState, tasks};
+#[cfg(feature = "shutdown")]
+pub use self::shutdown::*;
+#[cfg(all(feature = "rt", feature = "sync"))]
+pub use self::supervisor::*;
+#[cfg(all(feature = "rt", feature = "sync"))]
+pub use self::{blocking::*, bounded::*};
+
+/// Spawns a task and registers it under `name` along with the caller location
+/// for introspection with [`tasks`]. With `tokio_unstable`, the name is also
+/// given to the Tokio task.
+///
+/// # Panics
+///
+/// This method panics if called outside of a Tokio runtime.
+#[cfg(tokio_unstable)]
+#[cfg(feature = "rt")]
+#[track_caller]
+pub fn spawn_named<F>(name: &str, future: F) -> tokio::task::JoinHandle<F::Output>
+where
+    F: std::future::Future + Send + 'static,
+    F::Output: Send + 'static,
+{
+    let future = registry::register(name, std::panic::Location::caller(), future);
+    tokio::task::Builder::new()
+        .name(name)
+        .spawn(future)
+        .unwrap()
+}
+
+#[cfg(not(tokio_unstable))]
+#[cfg(feature = "rt")]
+#[track_caller]
+pub fn spawn_named<F>(name: &str, future: F) -> tokio::task::JoinHandle<F::Output>
+where
+    F: std::future::Future + Send + 'static,
+    F::Output: Send + 'static,
+{
+    let future = registry::register(name, std::panic::Location::caller(), future);
+    tokio::task::spawn(future)
+}
+
+/// Runs `f` on Tokio's blocking pool. With `tokio_unstable`, the name is given
+/// to the Tokio task. See [`BlockingPool`] for a dedicated, bounded pool.
+///
+/// # Panics
+///
+/// This method panics if called outside of a Tokio runtime.
+#[cfg(tokio_unstable)]
+#[cfg(feature = "rt")]
+pub fn spawn_blocking_named<F, R>(name: &str, f: F) -> tokio::task::JoinHandle<R>
+where
+    F: FnOnce() -> R + Send + 'static,
+    R: Send + 'static,
+{
+    tokio::task::Builder::new()
+        .name(name)
+        .spawn_blocking(f)
+        .unwrap()
+}
+
+#[cfg(not(tokio_unstable))]
+#[cfg(feature = "rt")]
+pub fn spawn_blocking_named<F, R>(_name: &str, f: F) -> tokio::task::JoinHandle<R>
+where
+    F: FnOnce() -> R + Send + 'static,
+    R: Send + 'static,
+{
+    tokio::task::spawn_blocking(f)
+}
@@ -0,0 +1,147 @@
+use std::{
+    any::Any,
+    panic::{AssertUnwindSafe, Location},
+    sync::{PoisonError, RwLock},
+};
+
+use futures_util::FutureExt;
+use tokio::{sync::mpsc, task::JoinHandle};
+#[cfg(feature = "shutdown")]
+use tokio_util::sync::CancellationToken;
+use tracing::error;
+
+use super::spawn_named;
+
+static POLICY: RwLock<PanicPolicy> = RwLock::new(PanicPolicy::new());
+
+/// A panic caught by [`spawn_named_catch_panic`].
+#[derive(Clone, Debug)]
+pub struct TaskPanic {
+    pub name: String,
+    /// Where the task was spawned.
+    pub location: &'static Location<'static>,
+    /// The panic payload if it is a string.
+    pub message: String,
+}
+
+/// What happens when a task spawned with [`spawn_named_catch_panic`] panics,
+/// in addition to logging the panic. Set with [`set_panic_policy`].
+#[derive(Clone, Debug, Default)]
+pub struct PanicPolicy {
+    sender: Option<mpsc::UnboundedSender<TaskPanic>>,
+    #[cfg(feature = "shutdown")]
+    shutdown: Option<CancellationToken>,
+}
+
+impl PanicPolicy {
+    /// Only logs panics.
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            sender: None,
+            #[cfg(feature = "shutdown")]
+            shutdown: None,
+        }
+    }
+
+    /// Forwards panics to a channel, e.g. for reporting.
+    #[must_use]
+    pub fn forward_to(mut self, sender: mpsc::UnboundedSender<TaskPanic>) -> Self {
+        self.sender = Some(sender);
+        self
+    }
+
+    /// Cancels a token on panic, e.g. [`super::Shutdown::token`] to shut the
+    /// process down.
+    #[cfg(feature = "shutdown")]
+    #[must_use]
+    pub fn shutdown_on_panic(mut self, token: CancellationToken) -> Self {
+        self.shutdown = Some(token);
+        self
+    }
+}
+
+/// Sets the process-wide policy for panics caught by
+/// [`spawn_named_catch_panic`].
+pub fn set_panic_policy(policy: PanicPolicy) {
+    *POLICY.write().unwrap_or_else(PoisonError::into_inner) = policy;
+}
+
+/// Like [`spawn_named`], but catches a panic of the task and handles it
+/// according to the [`PanicPolicy`]. The task then completes with `None`.
+///
+/// # Panics
+///
+/// This method panics if called outside of a Tokio runtime.
+#[track_caller]
+pub fn spawn_named_catch_panic<F>(name: &str, future: F) -> JoinHandle<Option<F::Output>>
+where
+    F: Future + Send + 'static,
+    F::Output: Send + 'static,
+{
+    let location = Location::caller();
+    let owned_name = name.to_owned();
+    spawn_named(name, async move {
+        match AssertUnwindSafe(future).catch_unwind().await {
+            Ok(output) => Some(output),
+            Err(payload) => {
+                handle_panic(TaskPanic {
+                    name: owned_name,
+                    location,
+                    message: payload_message(&*payload),
+                });
+                None
+            }
+        }
+    })
+}
+
+fn handle_panic(panic: TaskPanic) {
+    error!(
+        task = panic.name,
+        file = panic.location.file(),
+        line = panic.location.line(),
+        payload = panic.message,
+        "Task panicked."
+    );
+
+    let policy = POLICY.read().unwrap_or_else(PoisonError::into_inner);
+    #[cfg(feature = "shutdown")]
+    if let Some(token) = &policy.shutdown {
+        token.cancel();
+    }
+    if let Some(sender) = &policy.sender {
+        // Fails only if the receiver was dropped, in which case nobody is
+        // interested in the panic beyond the log.
+        let _ = sender.send(panic);
+    }
+}
+
+pub(super) fn payload_message(payload: &(dyn Any + Send)) -> String {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        (*message).to_owned()
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "Box<dyn Any>".to_owned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn spawn_named_catch_panic_forwards_panic() {
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        set_panic_policy(PanicPolicy::new().forward_to(tx));
+
+        let handle = spawn_named_catch_panic("panicking", async { panic!("boom") });
+        assert_eq!(handle.await.unwrap(), None::<()>);
+
+        let panic = rx.recv().await.unwrap();
+        assert_eq!(panic.name, "panicking");
+        assert_eq!(panic.location.file(), file!());
+        assert_eq!(panic.message, "boom");
+    }
+}
@@ -0,0 +1,337 @@
+use std::{
+    future::poll_fn,
+    hash::{BuildHasher, RandomState},
+    pin::pin,
+    task::Poll,
+    time::Duration,
+};
+
+use tokio::{
+    sync::watch,
+    task::JoinError,
+    time::{Instant, sleep_until},
+};
+use tracing::{error, warn};
+
+use super::{JoinSet, spawn_named};
+use crate::sync::LogThrottle;
+
+/// What happens when a tick is due while the previous run is still in
+/// progress.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum OverlapPolicy {
+    /// Skip the tick.
+    #[default]
+    Skip,
+    /// Run once the previous runs completed, catching up on every missed
+    /// tick.
+    Queue,
+    /// Abort the previous run and start a new one.
+    CancelPrevious,
+}
+
+/// Builder for a periodic task. See [`spawn_periodic`] for the common case.
+#[derive(Clone, Debug)]
+pub struct Periodic {
+    period: Duration,
+    overlap: OverlapPolicy,
+    jitter: Duration,
+    initial_delay: Duration,
+}
+
+#[derive(Clone, Copy)]
+struct Control {
+    period: Duration,
+    paused: bool,
+}
+
+enum Event {
+    // Whether the handle is still alive.
+    Changed(bool),
+    Tick,
+    RunCompleted(Result<(), JoinError>),
+}
+
+impl Periodic {
+    /// # Panics
+    ///
+    /// Panics if `period` is 0.
+    #[must_use]
+    pub fn new(period: Duration) -> Self {
+        assert!(!period.is_zero(), "periodic task requires period > 0");
+        Self {
+            period,
+            overlap: OverlapPolicy::default(),
+            jitter: Duration::ZERO,
+            initial_delay: Duration::ZERO,
+        }
+    }
+
+    #[must_use]
+    pub fn overlap(mut self, overlap: OverlapPolicy) -> Self {
+        self.overlap = overlap;
+        self
+    }
+
+    /// Delays every tick by a random duration of up to `jitter`, e.g. to
+    /// spread out requests of many instances.
+    #[must_use]
+    pub fn jitter(mut self, jitter: Duration) -> Self {
+        self.jitter = jitter;
+        self
+    }
+
+    /// Delays the first tick, which is otherwise due immediately.
+    #[must_use]
+    pub fn initial_delay(mut self, initial_delay: Duration) -> Self {
+        self.initial_delay = initial_delay;
+        self
+    }
+
+    /// Spawns a task with [`spawn_named`] which spawns a run of the future
+    /// returned by `f` on every tick.
+    ///
+    /// Dropping the returned handle aborts the task along with the run in
+    /// progress.
+    ///
+    /// # Panics
+    ///
+    /// This method panics if called outside of a Tokio runtime.
+    #[track_caller]
+    pub fn spawn<F, Fut>(self, name: &str, f: F) -> PeriodicHandle
+    where
+        F: FnMut() -> Fut + Send + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        let (control, rx) = watch::channel(Control {
+            period: self.period,
+            paused: false,
+        });
+        let mut driver = JoinSet::new();
+        driver.insert(spawn_named(name, self.run(name.to_owned(), rx, f)));
+        PeriodicHandle { control, driver }
+    }
+
+    async fn run<F, Fut>(self, name: String, mut control: watch::Receiver<Control>, mut f: F)
+    where
+        F: FnMut() -> Fut + Send + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        let random = RandomState::new();
+        let skip_log_throttle = LogThrottle::default();
+        let mut runs = JoinSet::new();
+        let mut queued = 0usize;
+        let mut ticks = 0u64;
+        let mut next_tick = Instant::now() + self.initial_delay;
+        loop {
+            let Control { period, paused } = *control.borrow_and_update();
+            let jitter = if self.jitter.is_zero() {
+                Duration::ZERO
+            } else {
+                let jitter_ns = u64::try_from(self.jitter.as_nanos()).unwrap_or(u64::MAX);
+                Duration::from_nanos(random.hash_one(ticks) % jitter_ns)
+            };
+
+            // Polled in order, so that control changes apply before a tick.
+            let event = {
+                let mut changed = pin!(control.changed());
+                let mut tick = pin!(sleep_until(next_tick + jitter));
+                poll_fn(|cx| {
+                    if let Poll::Ready(changed) = changed.as_mut().poll(cx) {
+                        return Poll::Ready(Event::Changed(changed.is_ok()));
+                    }
+                    if !paused && tick.as_mut().poll(cx).is_ready() {
+                        return Poll::Ready(Event::Tick);
+                    }
+                    if let Poll::Ready(Some((_, result))) = runs.poll_join_next(cx) {
+                        return Poll::Ready(Event::RunCompleted(result));
+                    }
+                    Poll::Pending
+                })
+                .await
+            };
+
+            match event {
+                Event::Changed(false) => {
+                    // The handle was dropped.
+                    return;
+                }
+                Event::Changed(true) => {
+                    if control.borrow().period != period {
+                        next_tick = Instant::now() + control.borrow().period;
+                    }
+                }
+                Event::Tick => {
+                    ticks += 1;
+                    next_tick += period;
+                    // Do not burst to catch up after a pause or a stall.
+                    let now = Instant::now();
+                    if next_tick < now {
+                        next_tick = now + period;
+                    }
+
+                    if runs.is_empty() {
+                        runs.insert(spawn_named(&name, f()));
+                        continue;
+                    }
+                    match self.overlap {
+                        OverlapPolicy::Skip => {
+                            if let Some(suppressed) = skip_log_throttle.check() {
+                                warn!(
+                                    task = name,
+                                    suppressed = suppressed,
+                                    "Skipped periodic run; previous run still in progress."
+                                );
+                            }
+                        }
+                        OverlapPolicy::Queue => queued += 1,
+                        OverlapPolicy::CancelPrevious => {
+                            runs.abort_all();
+                            runs.insert(spawn_named(&name, f()));
+                        }
+                    }
+                }
+                Event::RunCompleted(result) => {
+                    match result {
+                        Ok(()) => {}
+                        Err(e) if e.is_panic() => {
+                            error!(task = name, "Periodic run panicked.");
+                        }
+                        // Aborted to start a new run.
+                        Err(_) if self.overlap == OverlapPolicy::CancelPrevious => {}
+                        Err(_) => warn!(task = name, "Periodic run cancelled."),
+                    }
+                    if queued > 0 && runs.is_empty() {
+                        queued -= 1;
+                        runs.insert(spawn_named(&name, f()));
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Spawns a task which runs the future returned by `f` every `period`,
+/// starting immediately. See [`Periodic`] for jitter and an initial delay.
+///
+/// # Panics
+///
+/// This method panics if called outside of a Tokio runtime or if `period` is
+/// 0.
+#[track_caller]
+pub fn spawn_periodic<F, Fut>(
+    name: &str,
+    period: Duration,
+    overlap: OverlapPolicy,
+    f: F,
+) -> PeriodicHandle
+where
+    F: FnMut() -> Fut + Send + 'static,
+    Fut: Future<Output = ()> + Send + 'static,
+{
+    Periodic::new(period).overlap(overlap).spawn(name, f)
+}
+
+/// Controls a task spawned by [`spawn_periodic`]. Dropping the handle aborts
+/// the task.
+pub struct PeriodicHandle {
+    control: watch::Sender<Control>,
+    driver: JoinSet<()>,
+}
+
+impl PeriodicHandle {
+    /// Stops ticking. A run in progress is not affected.
+    pub fn pause(&self) {
+        self.control.send_modify(|control| control.paused = true);
+    }
+
+    /// Resumes ticking. A tick which became due while paused runs
+    /// immediately.
+    pub fn resume(&self) {
+        self.control.send_modify(|control| control.paused = false);
+    }
+
+    #[must_use]
+    pub fn is_paused(&self) -> bool {
+        self.control.borrow().paused
+    }
+
+    /// Changes the period. The next tick is due one new period from now.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `period` is 0.
+    pub fn set_period(&self, period: Duration) {
+        assert!(!period.is_zero(), "periodic task requires period > 0");
+        self.control.send_modify(|control| control.period = period);
+    }
+
+    #[must_use]
+    pub fn period(&self) -> Duration {
+        self.control.borrow().period
+    }
+
+    /// Aborts the task along with the run in progress and waits for them to
+    /// stop.
+    pub async fn stop(mut self) {
+        self.driver.abort_all();
+        while self.driver.join_next().await.is_some() {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+    };
+
+    use tokio::time::{Sleep, sleep};
+
+    use super::*;
+
+    fn counting(runs: Arc<AtomicUsize>, duration: Duration) -> impl FnMut() -> Sleep + Send {
+        move || {
+            runs.fetch_add(1, Ordering::Relaxed);
+            sleep(duration)
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn spawn_periodic_skips_overlapping_ticks() {
+        let runs = Arc::new(AtomicUsize::new(0));
+        let handle = spawn_periodic(
+            "periodic",
+            Duration::from_secs(10),
+            OverlapPolicy::Skip,
+            counting(runs.clone(), Duration::from_secs(15)),
+        );
+
+        // Runs at 0s and 20s; the ticks at 10s and 30s overlap.
+        sleep(Duration::from_secs(35)).await;
+        assert_eq!(runs.load(Ordering::Relaxed), 2);
+        handle.stop().await;
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn periodic_handle_pauses_and_changes_period() {
+        let runs = Arc::new(AtomicUsize::new(0));
+        let handle = spawn_periodic(
+            "periodic",
+            Duration::from_secs(10),
+            OverlapPolicy::Queue,
+            counting(runs.clone(), Duration::ZERO),
+        );
+        sleep(Duration::from_secs(5)).await;
+        assert_eq!(runs.load(Ordering::Relaxed), 1);
+
+        handle.pause();
+        sleep(Duration::from_secs(30)).await;
+        assert_eq!(runs.load(Ordering::Relaxed), 1);
+
+        handle.set_period(Duration::from_secs(1));
+        handle.resume();
+        sleep(Duration::from_millis(3500)).await;
+        assert_eq!(runs.load(Ordering::Relaxed), 4);
+    }
+}
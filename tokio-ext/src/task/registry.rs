@@ -0,0 +1,149 @@
+use std::{
+    collections::BTreeMap,
+    panic::Location,
+    pin::Pin,
+    sync::{
+        Arc, Mutex, MutexGuard, PoisonError,
+        atomic::{AtomicBool, AtomicU64, Ordering},
+    },
+    task::{Context, Poll},
+    time::{Duration, Instant},
+};
+
+use pin_project_lite::pin_project;
+
+/// Live tasks spawned with [`super::spawn_named`], by registration order.
+static REGISTRY: Mutex<BTreeMap<u64, Arc<Entry>>> = Mutex::new(BTreeMap::new());
+static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+
+struct Entry {
+    name: String,
+    location: &'static Location<'static>,
+    started: Instant,
+    running: AtomicBool,
+}
+
+/// Whether a task is currently being polled.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TaskState {
+    Idle,
+    Running,
+}
+
+/// Point-in-time view of a live task.
+#[derive(Clone, Debug)]
+pub struct TaskInfo {
+    /// Unique within the process, in spawn order.
+    pub id: u64,
+    pub name: String,
+    /// Where the task was spawned.
+    pub location: &'static Location<'static>,
+    pub started: Instant,
+    pub state: TaskState,
+}
+
+impl TaskInfo {
+    #[must_use]
+    pub fn age(&self) -> Duration {
+        self.started.elapsed()
+    }
+}
+
+/// Returns the live tasks spawned with [`super::spawn_named`], oldest first.
+/// Tasks are removed once they complete, panic or are aborted.
+#[must_use]
+pub fn tasks() -> Vec<TaskInfo> {
+    registry()
+        .iter()
+        .map(|(id, entry)| TaskInfo {
+            id: *id,
+            name: entry.name.clone(),
+            location: entry.location,
+            started: entry.started,
+            state: if entry.running.load(Ordering::Relaxed) {
+                TaskState::Running
+            } else {
+                TaskState::Idle
+            },
+        })
+        .collect()
+}
+
+fn registry() -> MutexGuard<'static, BTreeMap<u64, Arc<Entry>>> {
+    REGISTRY.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+pub(super) fn register<F>(
+    name: &str,
+    location: &'static Location<'static>,
+    future: F,
+) -> Registered<F> {
+    let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+    let entry = Arc::new(Entry {
+        name: name.to_owned(),
+        location,
+        started: Instant::now(),
+        running: AtomicBool::new(false),
+    });
+    registry().insert(id, entry.clone());
+    Registered {
+        future,
+        entry,
+        _deregister: Deregister(id),
+    }
+}
+
+pin_project! {
+    pub(super) struct Registered<F> {
+        #[pin]
+        future: F,
+        entry: Arc<Entry>,
+        _deregister: Deregister,
+    }
+}
+
+impl<F> Future for Registered<F>
+where
+    F: Future,
+{
+    type Output = F::Output;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.project();
+        this.entry.running.store(true, Ordering::Relaxed);
+        let poll = this.future.poll(cx);
+        this.entry.running.store(false, Ordering::Relaxed);
+        poll
+    }
+}
+
+// Removes the task from the registry when its future is dropped, i.e. on
+// completion, panic or abort.
+struct Deregister(u64);
+
+impl Drop for Deregister {
+    fn drop(&mut self) {
+        registry().remove(&self.0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::task::spawn_named;
+
+    #[tokio::test]
+    async fn tasks_lists_live_tasks() {
+        let name = "registry-test";
+        let handle = spawn_named(name, std::future::pending::<()>());
+        tokio::task::yield_now().await;
+
+        let task = tasks().into_iter().find(|task| task.name == name).unwrap();
+        assert_eq!(task.location.file(), file!());
+        assert_eq!(task.state, TaskState::Idle);
+
+        handle.abort();
+        let _ = handle.await;
+        assert!(tasks().iter().all(|task| task.name != name));
+    }
+}
@@ -0,0 +1,200 @@
+use std::{io, time::Duration};
+
+use tokio::{
+    task::JoinHandle,
+    time::{Instant, timeout_at},
+};
+use tokio_util::sync::CancellationToken;
+use tracing::{info, warn};
+
+use super::{JoinSet, spawn_named};
+
+/// Coordinates a graceful shutdown: tasks registered with the coordinator get
+/// a child [`CancellationToken`], which is cancelled on shutdown, and are given
+/// until a deadline to drain before the stragglers are aborted.
+///
+/// ```no_run
+/// # async fn run() {
+/// use std::time::Duration;
+///
+/// use tokio_ext::task::Shutdown;
+///
+/// let mut shutdown = Shutdown::new();
+/// shutdown.spawn("writer", |token| async move {
+///     token.cancelled().await;
+///     // Flush pending writes.
+/// });
+/// shutdown.wait_for_signal().await.unwrap();
+/// let report = shutdown.shutdown(Duration::from_secs(5)).await;
+/// assert!(report.is_clean());
+/// # }
+/// ```
+#[derive(Default)]
+pub struct Shutdown {
+    token: CancellationToken,
+    tasks: JoinSet<(), String>,
+}
+
+/// Outcome of [`Shutdown::shutdown`], listing task names.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ShutdownReport {
+    /// Finished before the deadline.
+    pub completed: Vec<String>,
+    /// Still running at the deadline and aborted.
+    pub timed_out: Vec<String>,
+    pub panicked: Vec<String>,
+    /// Aborted by other means than the coordinator, e.g. through an
+    /// [`AbortHandle`](tokio::task::AbortHandle) of a registered task.
+    pub cancelled: Vec<String>,
+}
+
+impl ShutdownReport {
+    /// Whether every task finished before the deadline without panicking or
+    /// being aborted.
+    #[must_use]
+    pub fn is_clean(&self) -> bool {
+        self.timed_out.is_empty() && self.panicked.is_empty() && self.cancelled.is_empty()
+    }
+}
+
+impl Shutdown {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns a token cancelled on shutdown, for tasks not spawned by the
+    /// coordinator.
+    #[must_use]
+    pub fn token(&self) -> CancellationToken {
+        self.token.child_token()
+    }
+
+    #[must_use]
+    pub fn is_shutting_down(&self) -> bool {
+        self.token.is_cancelled()
+    }
+
+    /// Spawns a task with [`spawn_named`], passing it a token cancelled on
+    /// shutdown. The task is expected to return soon after.
+    ///
+    /// # Panics
+    ///
+    /// This method panics if called outside of a Tokio runtime.
+    #[track_caller]
+    pub fn spawn<F, Fut>(&mut self, name: impl Into<String>, f: F)
+    where
+        F: FnOnce(CancellationToken) -> Fut,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        let name = name.into();
+        let handle = spawn_named(&name, f(self.token()));
+        self.tasks.insert_with_key(name, handle);
+    }
+
+    /// Registers an already spawned task, which should observe
+    /// [`Self::token`].
+    pub fn register(&mut self, name: impl Into<String>, handle: JoinHandle<()>) {
+        self.tasks.insert_with_key(name.into(), handle);
+    }
+
+    /// Waits for SIGINT (Ctrl-C) or, on Unix, SIGTERM.
+    pub async fn wait_for_signal(&self) -> io::Result<()> {
+        #[cfg(unix)]
+        {
+            use tokio::signal::unix::{SignalKind, signal};
+
+            let mut sigterm = signal(SignalKind::terminate())?;
+            tokio::select! {
+                result = tokio::signal::ctrl_c() => {
+                    result?;
+                    info!("Received SIGINT; shutting down.");
+                }
+                _ = sigterm.recv() => info!("Received SIGTERM; shutting down."),
+            }
+        }
+        #[cfg(not(unix))]
+        {
+            tokio::signal::ctrl_c().await?;
+            info!("Received SIGINT; shutting down.");
+        }
+        Ok(())
+    }
+
+    /// Cancels the tokens handed out and waits for registered tasks to finish
+    /// until `deadline` elapses, after which the remaining tasks are aborted.
+    pub async fn shutdown(mut self, deadline: Duration) -> ShutdownReport {
+        self.token.cancel();
+
+        let deadline = Instant::now() + deadline;
+        let mut report = ShutdownReport::default();
+        loop {
+            match timeout_at(deadline, self.tasks.join_next()).await {
+                Ok(Some((name, Ok(())))) => report.completed.push(name),
+                Ok(Some((name, Err(e)))) if e.is_panic() => {
+                    warn!(task = name, "Task panicked during shutdown.");
+                    report.panicked.push(name);
+                }
+                Ok(Some((name, Err(_)))) => {
+                    warn!(task = name, "Task cancelled during shutdown.");
+                    report.cancelled.push(name);
+                }
+                Ok(None) => break,
+                Err(_) => {
+                    self.tasks.abort_all();
+                    while let Some((name, result)) = self.tasks.join_next().await {
+                        match result {
+                            Ok(()) => report.completed.push(name),
+                            Err(e) if e.is_panic() => report.panicked.push(name),
+                            Err(_) => {
+                                warn!(task = name, "Task aborted after shutdown deadline.");
+                                report.timed_out.push(name);
+                            }
+                        }
+                    }
+                    break;
+                }
+            }
+        }
+        report
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::future::pending;
+
+    use super::*;
+
+    #[tokio::test(start_paused = true)]
+    async fn shutdown_reports_task_outcomes() {
+        let mut shutdown = Shutdown::new();
+        shutdown.spawn("clean", |token| async move { token.cancelled().await });
+        shutdown.spawn("stuck", |_| pending());
+        shutdown.spawn("panicking", |token| async move {
+            token.cancelled().await;
+            panic!("boom");
+        });
+
+        let report = shutdown.shutdown(Duration::from_secs(1)).await;
+        assert_eq!(report.completed, vec!["clean"]);
+        assert_eq!(report.timed_out, vec!["stuck"]);
+        assert_eq!(report.panicked, vec!["panicking"]);
+        assert!(!report.is_clean());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn shutdown_reports_task_aborted_from_outside() {
+        let mut shutdown = Shutdown::new();
+        let token = shutdown.token();
+        let handle = tokio::spawn(async move { token.cancelled().await });
+        let abort_handle = handle.abort_handle();
+        shutdown.register("aborted", handle);
+        abort_handle.abort();
+
+        let report = shutdown.shutdown(Duration::from_secs(1)).await;
+        assert!(report.completed.is_empty());
+        assert_eq!(report.cancelled, vec!["aborted"]);
+        assert!(!report.is_clean());
+    }
+}
@@ -0,0 +1,420 @@
+use std::{collections::VecDeque, error::Error, time::Duration};
+
+use futures_util::{FutureExt, future::BoxFuture};
+use std_ext::iter::{ZeroThenExponentialWithReset, zero_then_exponential_with_reset};
+use tokio::{
+    sync::broadcast,
+    task::{JoinError, JoinHandle},
+    time::{Instant, sleep},
+};
+use tracing::{error, warn};
+
+use super::{JoinSet, spawn_named};
+
+/// Capacity of the lifecycle event channel. Subscribers falling further
+/// behind observe [`broadcast::error::RecvError::Lagged`].
+pub const EVENT_CAPACITY: usize = 64;
+
+pub type ChildError = Box<dyn Error + Send + Sync>;
+
+type ChildFactory = Box<dyn FnMut() -> BoxFuture<'static, Result<(), ChildError>> + Send>;
+
+/// Which children are restarted when one exits.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Strategy {
+    /// Restart only the exited child.
+    OneForOne,
+    /// Abort the remaining children and restart all of them.
+    OneForAll,
+}
+
+/// When a child is restarted.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Restart {
+    /// Always, even if it completed successfully.
+    Permanent,
+    /// Only if it returned an error, panicked or was cancelled.
+    Transient,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ExitReason {
+    Completed,
+    Failed(String),
+    Panicked,
+    Cancelled,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SupervisorEvent {
+    /// The child's future is about to be polled for the first time, i.e.
+    /// after the restart delay.
+    Started {
+        child: String,
+    },
+    Exited {
+        child: String,
+        reason: ExitReason,
+    },
+    Restarting {
+        child: String,
+        delay: Duration,
+    },
+    /// More than the allowed number of restarts happened within the intensity
+    /// period; all children were aborted.
+    GaveUp {
+        restarts: usize,
+        period: Duration,
+    },
+}
+
+#[derive(thiserror::Error, Debug, PartialEq, Eq)]
+pub enum SupervisorError {
+    #[error("Supervisor gave up after {restarts} restarts within {period:?}.")]
+    GaveUp { restarts: usize, period: Duration },
+}
+
+/// Restarts long-lived tasks (children) when they exit, in the spirit of
+/// Erlang supervisors.
+///
+/// Each child has its own backoff, yielding delays of 0, 1, 2, 4 ... times the
+/// backoff unit, capped at the maximum and reset once the child has not been
+/// restarted for the reset delay. If more than `max_restarts` restarts happen
+/// within the intensity period, the supervisor aborts all children and gives
+/// up.
+pub struct Supervisor {
+    name: String,
+    strategy: Strategy,
+    children: Vec<Child>,
+    backoff_unit: Duration,
+    backoff_max: Duration,
+    backoff_reset: Duration,
+    max_restarts: usize,
+    period: Duration,
+    events: broadcast::Sender<SupervisorEvent>,
+}
+
+struct Child {
+    name: String,
+    restart: Restart,
+    factory: ChildFactory,
+    backoff: ZeroThenExponentialWithReset,
+}
+
+impl Supervisor {
+    /// Creates a supervisor with a backoff unit of 1s capped at 1 min and
+    /// reset after 5 min, allowing 10 restarts per min.
+    #[must_use]
+    pub fn new(name: impl Into<String>, strategy: Strategy) -> Self {
+        Self {
+            name: name.into(),
+            strategy,
+            children: Vec::new(),
+            backoff_unit: Duration::from_secs(1),
+            backoff_max: Duration::from_secs(60),
+            backoff_reset: Duration::from_secs(300),
+            max_restarts: 10,
+            period: Duration::from_secs(60),
+            events: broadcast::channel(EVENT_CAPACITY).0,
+        }
+    }
+
+    /// Adds a child, spawned with [`spawn_named`] on every (re)start with a
+    /// future returned by `factory`.
+    #[must_use]
+    pub fn child<F, Fut, E>(
+        mut self,
+        name: impl Into<String>,
+        restart: Restart,
+        mut factory: F,
+    ) -> Self
+    where
+        F: FnMut() -> Fut + Send + 'static,
+        Fut: Future<Output = Result<(), E>> + Send + 'static,
+        E: Into<ChildError>,
+    {
+        self.children.push(Child {
+            name: name.into(),
+            restart,
+            factory: Box::new(move || factory().map(|result| result.map_err(Into::into)).boxed()),
+            backoff: zero_then_exponential_with_reset(self.backoff_reset),
+        });
+        self
+    }
+
+    /// Sets the backoff of all children, including those already added.
+    #[must_use]
+    pub fn backoff(mut self, unit: Duration, max: Duration, reset_after: Duration) -> Self {
+        self.backoff_unit = unit;
+        self.backoff_max = max;
+        self.backoff_reset = reset_after;
+        for child in &mut self.children {
+            child.backoff = zero_then_exponential_with_reset(reset_after);
+        }
+        self
+    }
+
+    /// Sets the maximum number of restarts within `period` before the
+    /// supervisor gives up.
+    #[must_use]
+    pub fn intensity(mut self, max_restarts: usize, period: Duration) -> Self {
+        self.max_restarts = max_restarts;
+        self.period = period;
+        self
+    }
+
+    /// Subscribes to lifecycle events. Subscribe before [`Self::spawn`] to not
+    /// miss the first events.
+    #[must_use]
+    pub fn subscribe(&self) -> broadcast::Receiver<SupervisorEvent> {
+        self.events.subscribe()
+    }
+
+    /// Spawns the supervisor, which starts all children. Aborting the returned
+    /// handle aborts all children.
+    ///
+    /// # Panics
+    ///
+    /// This method panics if called outside of a Tokio runtime.
+    #[track_caller]
+    pub fn spawn(self) -> JoinHandle<Result<(), SupervisorError>> {
+        let name = self.name.clone();
+        spawn_named(&name, self.run())
+    }
+
+    async fn run(mut self) -> Result<(), SupervisorError> {
+        let mut running = JoinSet::new();
+        for index in 0..self.children.len() {
+            self.start(&mut running, index, Duration::ZERO);
+        }
+
+        let mut restarts = VecDeque::new();
+        while let Some((index, result)) = running.join_next().await {
+            let reason = exit_reason(result);
+            let child = &self.children[index];
+            match &reason {
+                ExitReason::Completed => {}
+                ExitReason::Failed(e) => {
+                    warn!(
+                        supervisor = self.name,
+                        child = child.name,
+                        error = e,
+                        "Child failed."
+                    );
+                }
+                ExitReason::Panicked | ExitReason::Cancelled => {
+                    error!(
+                        supervisor = self.name,
+                        child = child.name,
+                        reason = ?reason,
+                        "Child exited abnormally."
+                    );
+                }
+            }
+            let restart = child.restart == Restart::Permanent || reason != ExitReason::Completed;
+            self.emit(SupervisorEvent::Exited {
+                child: child.name.clone(),
+                reason,
+            });
+            if !restart {
+                continue;
+            }
+
+            let now = Instant::now();
+            restarts.push_back(now);
+            while restarts
+                .front()
+                .is_some_and(|restart| *restart + self.period <= now)
+            {
+                restarts.pop_front();
+            }
+            if restarts.len() > self.max_restarts {
+                error!(
+                    supervisor = self.name,
+                    restarts = restarts.len(),
+                    "Supervisor gave up."
+                );
+                self.emit(SupervisorEvent::GaveUp {
+                    restarts: restarts.len(),
+                    period: self.period,
+                });
+                // Remaining children are aborted when `running` is dropped.
+                return Err(SupervisorError::GaveUp {
+                    restarts: restarts.len(),
+                    period: self.period,
+                });
+            }
+
+            let delay = self.next_delay(index);
+            match self.strategy {
+                Strategy::OneForOne => self.restart(&mut running, index, delay),
+                Strategy::OneForAll => {
+                    for other in 0..self.children.len() {
+                        if let Some(handle) = running.remove(&other) {
+                            handle.abort();
+                            // The child may have exited on its own before
+                            // the abort took effect.
+                            let reason = exit_reason(handle.await);
+                            self.emit(SupervisorEvent::Exited {
+                                child: self.children[other].name.clone(),
+                                reason,
+                            });
+                        }
+                    }
+                    for index in 0..self.children.len() {
+                        self.restart(&mut running, index, delay);
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn next_delay(&mut self, index: usize) -> Duration {
+        let n = self.children[index].backoff.next().unwrap_or(u64::MAX);
+        self.backoff_unit
+            .checked_mul(u32::try_from(n).unwrap_or(u32::MAX))
+            .unwrap_or(self.backoff_max)
+            .min(self.backoff_max)
+    }
+
+    fn restart(
+        &mut self,
+        running: &mut JoinSet<Result<(), ChildError>>,
+        index: usize,
+        delay: Duration,
+    ) {
+        self.emit(SupervisorEvent::Restarting {
+            child: self.children[index].name.clone(),
+            delay,
+        });
+        self.start(running, index, delay);
+    }
+
+    fn start(
+        &mut self,
+        running: &mut JoinSet<Result<(), ChildError>>,
+        index: usize,
+        delay: Duration,
+    ) {
+        let child = &mut self.children[index];
+        let future = (child.factory)();
+        let name = child.name.clone();
+        let events = self.events.clone();
+        running.insert_with_key(
+            index,
+            spawn_named(&child.name, async move {
+                sleep(delay).await;
+                emit(&events, SupervisorEvent::Started { child: name });
+                future.await
+            }),
+        );
+    }
+
+    fn emit(&self, event: SupervisorEvent) {
+        emit(&self.events, event);
+    }
+}
+
+fn emit(events: &broadcast::Sender<SupervisorEvent>, event: SupervisorEvent) {
+    // Fails only if there are no subscribers.
+    let _ = events.send(event);
+}
+
+fn exit_reason(result: Result<Result<(), ChildError>, JoinError>) -> ExitReason {
+    match result {
+        Ok(Ok(())) => ExitReason::Completed,
+        Ok(Err(e)) => ExitReason::Failed(e.to_string()),
+        Err(e) if e.is_panic() => ExitReason::Panicked,
+        Err(_) => ExitReason::Cancelled,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+    };
+
+    use super::*;
+
+    #[tokio::test(start_paused = true)]
+    async fn one_for_one_restarts_failed_child_with_backoff() {
+        let starts = Arc::new(AtomicUsize::new(0));
+        let supervisor = Supervisor::new("test", Strategy::OneForOne)
+            .backoff(
+                Duration::from_secs(7),
+                Duration::from_secs(7),
+                Duration::from_secs(7),
+            )
+            .child("child", Restart::Transient, {
+                let starts = starts.clone();
+                move || {
+                    let start = starts.fetch_add(1, Ordering::Relaxed);
+                    async move { if start < 2 { Err("boom") } else { Ok(()) } }
+                }
+            })
+            // Also applies to children added before.
+            .backoff(
+                Duration::from_secs(1),
+                Duration::from_secs(60),
+                Duration::from_secs(300),
+            );
+        let mut events = supervisor.subscribe();
+        supervisor.spawn().await.unwrap().unwrap();
+
+        let started = || SupervisorEvent::Started {
+            child: "child".to_owned(),
+        };
+        let failed = || SupervisorEvent::Exited {
+            child: "child".to_owned(),
+            reason: ExitReason::Failed("boom".to_owned()),
+        };
+        let mut received = Vec::new();
+        while let Ok(event) = events.try_recv() {
+            received.push(event);
+        }
+        assert_eq!(
+            received,
+            vec![
+                started(),
+                failed(),
+                // The first restart is immediate.
+                SupervisorEvent::Restarting {
+                    child: "child".to_owned(),
+                    delay: Duration::ZERO,
+                },
+                started(),
+                failed(),
+                SupervisorEvent::Restarting {
+                    child: "child".to_owned(),
+                    delay: Duration::from_secs(1),
+                },
+                started(),
+                SupervisorEvent::Exited {
+                    child: "child".to_owned(),
+                    reason: ExitReason::Completed,
+                },
+            ]
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn gives_up_when_intensity_exceeded() {
+        let supervisor = Supervisor::new("test", Strategy::OneForAll)
+            .intensity(2, Duration::from_secs(60))
+            .child("failing", Restart::Permanent, || async { Err("boom") })
+            .child("pending", Restart::Permanent, || {
+                std::future::pending::<Result<(), ChildError>>()
+            });
+
+        assert_eq!(
+            supervisor.spawn().await.unwrap(),
+            Err(SupervisorError::GaveUp {
+                restarts: 3,
+                period: Duration::from_secs(60),
+            })
+        );
+    }
+}
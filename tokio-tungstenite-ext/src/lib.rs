@@ -28,7 +28,8 @@ pub trait WebSocketStreamExt {
 
     /// Periodically reconnect to the server. During reconnection, duplicate
     /// messages may be received. It is up to the client to perform any
-    /// deduplication if necessary.
+    /// deduplication if necessary, for example with
+    /// `std_ext::iter::StreamDedupExt` (requires its `stream` feature).
     ///
     /// Note that this does not spawn any tasks. It is expected for the client
     /// to poll the stream for the functionality to take effect.